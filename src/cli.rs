@@ -0,0 +1,54 @@
+//! Small helpers for parsing `--flag value` style command lines.
+
+use std::str::FromStr;
+
+use ggez::GameError;
+use ggez::GameResult;
+
+/// Walks over command line arguments, turning mistakes into errors that
+/// include the usage text of the current command.
+pub struct Args<I: Iterator<Item = String>> {
+    args: I,
+    usage: &'static str,
+}
+
+impl<I: Iterator<Item = String>> Args<I> {
+    pub fn new(args: I, usage: &'static str) -> Self {
+        Args { args, usage }
+    }
+
+    /// The next flag, or `None` once all arguments are used up.
+    pub fn next_flag(&mut self) -> Option<String> {
+        self.args.next()
+    }
+
+    /// Parses the value following `flag`.
+    pub fn value<T: FromStr>(&mut self, flag: &str) -> GameResult<T> {
+        let value = self.string(flag)?;
+        value.parse().map_err(|_| self.error(&format!("invalid value {:?} for {}", value, flag)))
+    }
+
+    /// The raw value following `flag`.
+    pub fn string(&mut self, flag: &str) -> GameResult<String> {
+        match self.args.next() {
+            Some(value) => Ok(value),
+            None => Err(self.error(&format!("missing value for {}", flag))),
+        }
+    }
+
+    /// Parses a `WIDTHxHEIGHT` value following `flag`.
+    pub fn size(&mut self, flag: &str) -> GameResult<(f32, f32)> {
+        let value = self.string(flag)?;
+        let mut parts = value.splitn(2, 'x').map(|part| part.parse::<f32>());
+        match (parts.next(), parts.next()) {
+            (Some(Ok(width)), Some(Ok(height))) if width > 0.0 && height > 0.0 => {
+                Ok((width, height))
+            }
+            _ => Err(self.error(&format!("invalid size {:?} for {}", value, flag))),
+        }
+    }
+
+    pub fn error(&self, reason: &str) -> GameError {
+        GameError::ConfigError(format!("{}\n{}", reason, self.usage))
+    }
+}
@@ -0,0 +1,153 @@
+//! Commands typed into the developer console.
+//!
+//! Scenes register the commands they understand in `World::commands`,
+//! usually when they're entered. A line submitted to the console is split
+//! into words and run before the next update, by offering the command to
+//! each scene from the top of the stack down until one takes it:
+//!
+//! ```text
+//! teleport 512 180 512
+//! set fov 70
+//! ```
+
+use std::collections::VecDeque;
+
+/// Lines of output kept for the console to show
+const MAX_LOG_LINES: usize = 200;
+/// Lines of history kept for going back through with the arrow keys
+const MAX_HISTORY: usize = 100;
+
+/// A command a scene can be told to run.
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub name: &'static str,
+    /// The arguments it takes, like `X Y Z`
+    pub usage: &'static str,
+    /// What it does, in a few words
+    pub help: &'static str,
+    /// Values the first argument can take, for tab completion
+    pub completions: Vec<String>,
+}
+
+impl Command {
+    pub fn new(name: &'static str, usage: &'static str, help: &'static str) -> Self {
+        Command { name, usage, help, completions: Vec::new() }
+    }
+
+    pub fn with_completions<I: IntoIterator<Item = String>>(mut self, completions: I) -> Self {
+        self.completions = completions.into_iter().collect();
+        self
+    }
+
+    /// How to call it, for error messages and the help.
+    pub fn usage_text(&self) -> String {
+        if self.usage.is_empty() {
+            self.name.to_owned()
+        } else {
+            format!("{} {}", self.name, self.usage)
+        }
+    }
+}
+
+/// The registered commands, along with what's been typed and printed.
+#[derive(Debug, Default)]
+pub struct Commands {
+    registered: Vec<Command>,
+    pending: VecDeque<String>,
+    history: Vec<String>,
+    log: Vec<String>,
+}
+
+impl Commands {
+    /// Adds a command, replacing any other with the same name.
+    pub fn register(&mut self, command: Command) {
+        self.unregister(command.name);
+        self.registered.push(command);
+        self.registered.sort_by_key(|command| command.name);
+    }
+
+    pub fn unregister(&mut self, name: &str) {
+        self.registered.retain(|command| command.name != name);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.registered.iter().find(|command| command.name == name)
+    }
+
+    /// How a command is meant to be called, for when it's called wrong.
+    pub fn usage(&self, name: &str) -> String {
+        match self.get(name) {
+            Some(command) => format!("usage: {}", command.usage_text()),
+            None => format!("unknown command {}", name),
+        }
+    }
+
+    /// Every registered command, by name.
+    pub fn all(&self) -> &[Command] {
+        &self.registered
+    }
+
+    /// Queues a line to be run before the next update and remembers it in
+    /// the history.
+    pub fn submit(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        self.pending.push_back(line.to_owned());
+        if self.history.last().map(String::as_str) != Some(line) {
+            self.history.push(line.to_owned());
+        }
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+    }
+
+    pub(super) fn next_pending(&mut self) -> Option<String> {
+        self.pending.pop_front()
+    }
+
+    /// Lines submitted before, oldest first.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Adds output for the console to show.
+    pub fn print(&mut self, text: &str) {
+        self.log.extend(text.lines().map(str::to_owned));
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.drain(..self.log.len() - MAX_LOG_LINES);
+        }
+    }
+
+    /// The output so far, oldest first.
+    pub fn log(&self) -> &[String] {
+        &self.log
+    }
+
+    pub fn clear_log(&mut self) {
+        self.log.clear();
+    }
+
+    /// What the last word of a partly typed line could be: a command name
+    /// for the first word, or one of the command's completions for the
+    /// second.
+    pub fn complete(&self, line: &str) -> Vec<String> {
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        let partial = if line.is_empty() || line.ends_with(char::is_whitespace) {
+            ""
+        } else {
+            words.pop().unwrap_or("")
+        };
+
+        let mut candidates: Vec<String> = match words[..] {
+            [] => self.registered.iter().map(|command| command.name.to_owned()).collect(),
+            [name] => self.get(name).map(|command| command.completions.clone()).unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        candidates.retain(|candidate| candidate.starts_with(partial));
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+}
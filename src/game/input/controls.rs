@@ -0,0 +1,545 @@
+//! Reading and writing key bindings as text, so that players can
+//! rebind the controls.
+//!
+//! The controls file has one `action = input, input, ...` line per
+//! action. Buttons are named after their `Button` variant, and each axis
+//! has a `+` and a `-` action, like `Throttle+`. Inputs are `KeyCode`
+//! names, `MouseLeft`, `MouseRight`, `MouseMiddle` and `Mouse4` and up,
+//! `WheelUp`, `WheelDown`, `WheelLeft` and `WheelRight`, or gamepad buttons
+//! and axes prefixed with `Pad`, like `PadSouth` or `PadLeftStickY`.
+//! Inputs can require modifiers, joined with `+` like `Ctrl+Shift+S`.
+//! Actions left out of the file keep their default inputs, apart from any
+//! the file has given to another action.
+//!
+//! The first line holds the version of the format. Version 2 split `Pause`
+//! off `Quit`, so version 1 files are upgraded by moving `Escape` from
+//! `Quit` to `Pause` and quitting with `Ctrl+Q` instead.
+
+use std::collections::HashSet;
+use std::fs;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+
+use ggez::event::Axis as GamepadAxis;
+use ggez::event::Button as GamepadButton;
+use ggez::event::KeyCode;
+use ggez::event::KeyMods;
+use ggez::event::MouseButton;
+use ggez::Context;
+use ggez::GameError;
+use ggez::GameResult;
+
+use super::create_input_binding;
+use super::types::Chord;
+use super::types::InputEffect;
+use super::types::InputType;
+use super::types::WheelDirection;
+use super::Axis;
+use super::Binding;
+use super::Button;
+use super::Event;
+
+pub const CONTROLS_FILE: &str = "controls.cfg";
+const CONTROLS_VERSION: u32 = 2;
+/// Starts the first line of the file, followed by the version
+const CONTROLS_HEADER: &str = "# voxelspace controls v";
+
+/// Keys that can be bound, everything else is ignored by the controls file.
+const KEYS: &[KeyCode] = &[
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Key0,
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Escape,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Snapshot,
+    KeyCode::Scroll,
+    KeyCode::Pause,
+    KeyCode::Insert,
+    KeyCode::Home,
+    KeyCode::Delete,
+    KeyCode::End,
+    KeyCode::PageDown,
+    KeyCode::PageUp,
+    KeyCode::Left,
+    KeyCode::Up,
+    KeyCode::Right,
+    KeyCode::Down,
+    KeyCode::Back,
+    KeyCode::Return,
+    KeyCode::Space,
+    KeyCode::Tab,
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+    KeyCode::NumpadAdd,
+    KeyCode::NumpadSubtract,
+    KeyCode::NumpadMultiply,
+    KeyCode::NumpadDivide,
+    KeyCode::NumpadDecimal,
+    KeyCode::NumpadEnter,
+    KeyCode::Apostrophe,
+    KeyCode::Backslash,
+    KeyCode::Comma,
+    KeyCode::Equals,
+    KeyCode::Grave,
+    KeyCode::LBracket,
+    KeyCode::RBracket,
+    KeyCode::Minus,
+    KeyCode::Period,
+    KeyCode::Semicolon,
+    KeyCode::Slash,
+    KeyCode::LAlt,
+    KeyCode::RAlt,
+    KeyCode::LControl,
+    KeyCode::RControl,
+    KeyCode::LShift,
+    KeyCode::RShift,
+    KeyCode::LWin,
+    KeyCode::RWin,
+];
+
+/// Modifier names, in the order they're written.
+const MODIFIERS: &[(KeyMods, &str)] = &[
+    (KeyMods::CTRL, "Ctrl"),
+    (KeyMods::SHIFT, "Shift"),
+    (KeyMods::ALT, "Alt"),
+    (KeyMods::LOGO, "Logo"),
+];
+
+const WHEEL_DIRECTIONS: &[WheelDirection] =
+    &[WheelDirection::Up, WheelDirection::Down, WheelDirection::Left, WheelDirection::Right];
+
+const GAMEPAD_BUTTONS: &[GamepadButton] = &[
+    GamepadButton::South,
+    GamepadButton::East,
+    GamepadButton::North,
+    GamepadButton::West,
+    GamepadButton::C,
+    GamepadButton::Z,
+    GamepadButton::LeftTrigger,
+    GamepadButton::LeftTrigger2,
+    GamepadButton::RightTrigger,
+    GamepadButton::RightTrigger2,
+    GamepadButton::Select,
+    GamepadButton::Start,
+    GamepadButton::Mode,
+    GamepadButton::LeftThumb,
+    GamepadButton::RightThumb,
+    GamepadButton::DPadUp,
+    GamepadButton::DPadDown,
+    GamepadButton::DPadLeft,
+    GamepadButton::DPadRight,
+];
+
+const GAMEPAD_AXES: &[GamepadAxis] = &[
+    GamepadAxis::LeftStickX,
+    GamepadAxis::LeftStickY,
+    GamepadAxis::LeftZ,
+    GamepadAxis::RightStickX,
+    GamepadAxis::RightStickY,
+    GamepadAxis::RightZ,
+    GamepadAxis::DPadX,
+    GamepadAxis::DPadY,
+];
+
+/// Every action that can be bound, in the order they're listed to the
+/// player.
+pub fn actions() -> Vec<Event> {
+    let mut actions = Vec::new();
+    for axis in &Axis::ALL {
+        actions.push(InputEffect::Axis(*axis, true));
+        actions.push(InputEffect::Axis(*axis, false));
+    }
+    for button in &Button::ALL {
+        actions.push(InputEffect::Button(*button));
+    }
+    actions
+}
+
+/// Name of an action in the controls file.
+pub fn action_name(action: &Event) -> String {
+    match action {
+        InputEffect::Axis(axis, true) => format!("{:?}+", axis),
+        InputEffect::Axis(axis, false) => format!("{:?}-", axis),
+        InputEffect::Button(button) => format!("{:?}", button),
+        InputEffect::Pointer(..) => "Pointer".to_owned(),
+    }
+}
+
+/// Name of a physical input in the controls file.
+pub fn input_name(input: InputType) -> String {
+    match input {
+        InputType::KeyEvent(key) => format!("{:?}", key),
+        InputType::MouseButtonEvent(MouseButton::Other(n)) => format!("Mouse{}", n),
+        InputType::MouseButtonEvent(button) => format!("Mouse{:?}", button),
+        InputType::GamepadButtonEvent(button) => format!("Pad{:?}", button),
+        InputType::GamepadAxisEvent(axis) => format!("Pad{:?}", axis),
+        InputType::MouseWheelEvent(direction) => format!("Wheel{:?}", direction),
+    }
+}
+
+/// Name of a chord in the controls file, like `Ctrl+S`.
+pub fn chord_name(chord: Chord) -> String {
+    let mut name = String::new();
+    for (modifier, modifier_name) in MODIFIERS {
+        if chord.mods.contains(*modifier) {
+            name.push_str(modifier_name);
+            name.push('+');
+        }
+    }
+    name.push_str(&input_name(chord.input));
+    name
+}
+
+pub fn parse_action(name: &str) -> Option<Event> {
+    actions().into_iter().find(|action| action_name(action).eq_ignore_ascii_case(name))
+}
+
+pub fn parse_input(name: &str) -> Option<InputType> {
+    let mouse = [MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+    let inputs = KEYS
+        .iter()
+        .map(|key| InputType::KeyEvent(*key))
+        .chain(mouse.iter().map(|button| InputType::MouseButtonEvent(*button)))
+        .chain(WHEEL_DIRECTIONS.iter().map(|direction| InputType::MouseWheelEvent(*direction)))
+        .chain(GAMEPAD_BUTTONS.iter().map(|button| InputType::GamepadButtonEvent(*button)))
+        .chain(GAMEPAD_AXES.iter().map(|axis| InputType::GamepadAxisEvent(*axis)));
+    for input in inputs {
+        if input_name(input).eq_ignore_ascii_case(name) {
+            return Some(input);
+        }
+    }
+
+    let prefix = name.get(..5).filter(|prefix| prefix.eq_ignore_ascii_case("mouse"))?;
+    match name[prefix.len()..].parse() {
+        Ok(n) if n >= 4 => Some(InputType::MouseButtonEvent(MouseButton::Other(n))),
+        _ => None,
+    }
+}
+
+fn parse_chord(name: &str) -> Option<Chord> {
+    let mut parts: Vec<&str> = name.split('+').map(str::trim).collect();
+    let input = parse_input(parts.pop()?)?;
+    let mut mods = KeyMods::NONE;
+    for part in parts {
+        let (modifier, _) =
+            MODIFIERS.iter().find(|(_, modifier_name)| modifier_name.eq_ignore_ascii_case(part))?;
+        mods |= *modifier;
+    }
+    Some(Chord::new(mods, input))
+}
+
+/// Reads a controls file on top of the default bindings.
+pub fn load_input_binding<R: BufRead>(reader: R) -> GameResult<Binding> {
+    // files from before the header was written are version 1
+    let mut version = 1;
+    let mut listed: Vec<(Event, Vec<Chord>)> = Vec::new();
+    let mut seen_inputs = HashSet::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+
+        let parse_error = |reason: &str| {
+            GameError::ConfigError(format!("controls line {}: {} in {:?}", index + 1, reason, line))
+        };
+
+        if let Some(number) = line.strip_prefix(CONTROLS_HEADER) {
+            version = number.parse().map_err(|_| parse_error("invalid version"))?;
+            if version > CONTROLS_VERSION {
+                return Err(parse_error("written by a newer version of the game"));
+            }
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, inputs) = match line.split_once('=') {
+            Some((name, inputs)) => (name.trim(), inputs.trim()),
+            None => return Err(parse_error("expected action = inputs")),
+        };
+        let action =
+            parse_action(name).ok_or_else(|| parse_error(&format!("unknown action {:?}", name)))?;
+        if listed.iter().any(|(listed, _)| *listed == action) {
+            return Err(parse_error(&format!("{} is listed twice", name)));
+        }
+
+        // an empty list leaves the action unbound
+        let mut chords = Vec::new();
+        for input_name in inputs.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let chord = parse_chord(input_name)
+                .ok_or_else(|| parse_error(&format!("unknown input {:?}", input_name)))?;
+            if !seen_inputs.insert(chord) {
+                return Err(parse_error(&format!("{} is bound twice", input_name)));
+            }
+            chords.push(chord);
+        }
+        listed.push((action, chords));
+    }
+
+    if version < 2 {
+        upgrade_from_v1(&mut listed);
+    }
+
+    let mut binding = Binding::new();
+    for (action, chords) in &listed {
+        for chord in chords {
+            binding.bind(*chord, *action);
+        }
+    }
+
+    let defaults = create_input_binding();
+    for action in actions() {
+        if listed.iter().any(|(listed, _)| *listed == action) {
+            continue;
+        }
+        for chord in defaults.inputs_for(&action) {
+            match binding.bound_to(chord) {
+                Some(other) => eprintln!(
+                    "controls: {} isn't listed, and its default {} is bound to {} instead",
+                    action_name(&action),
+                    chord_name(chord),
+                    action_name(&other)
+                ),
+                None => binding.bind(chord, action),
+            }
+        }
+    }
+    Ok(binding)
+}
+
+/// Version 1 quit with Escape and had no pause menu. Escape moves over to
+/// `Pause` and `Quit` gets `Ctrl+Q` in its place, unless the file already
+/// uses it for something else.
+fn upgrade_from_v1(listed: &mut Vec<(Event, Vec<Chord>)>) {
+    let escape = Chord::from(InputType::KeyEvent(KeyCode::Escape));
+    let ctrl_q = Chord::new(KeyMods::CTRL, InputType::KeyEvent(KeyCode::Q));
+    let pause = InputEffect::Button(Button::Pause);
+    let ctrl_q_free = !listed.iter().any(|(_, chords)| chords.contains(&ctrl_q));
+    let pause_listed = listed.iter().any(|(action, _)| *action == pause);
+
+    let quit = listed.iter_mut().find(|(action, _)| *action == InputEffect::Button(Button::Quit));
+    let chords = match quit {
+        Some((_, chords)) if chords.contains(&escape) => chords,
+        _ => return,
+    };
+    chords.retain(|chord| *chord != escape);
+    if ctrl_q_free {
+        chords.push(ctrl_q);
+    }
+    if !pause_listed {
+        listed.push((pause, vec![escape]));
+    }
+}
+
+pub fn save_input_binding<W: Write>(binding: &Binding, mut writer: W) -> GameResult {
+    writeln!(writer, "{}{}", CONTROLS_HEADER, CONTROLS_VERSION)?;
+    for action in actions() {
+        let mut inputs: Vec<String> =
+            binding.inputs_for(&action).into_iter().map(chord_name).collect();
+        inputs.sort();
+        writeln!(writer, "{} = {}", action_name(&action), inputs.join(", "))?;
+    }
+    Ok(())
+}
+
+/// Writes the controls to the user config directory.
+pub fn save_controls(ctx: &Context, binding: &Binding) -> GameResult {
+    let dir = ggez::filesystem::user_config_dir(ctx);
+    fs::create_dir_all(dir)?;
+    save_input_binding(binding, File::create(dir.join(CONTROLS_FILE))?)
+}
+
+/// Inputs bound to an action, like `Up, W`, or `-` if there are none.
+pub fn inputs_text(binding: &Binding, action: &Event) -> String {
+    let mut inputs: Vec<String> = binding.inputs_for(action).into_iter().map(chord_name).collect();
+    if inputs.is_empty() {
+        return "-".to_owned();
+    }
+    inputs.sort();
+    inputs.join(", ")
+}
+
+/// One line per axis and button with the inputs bound to it, for on-screen
+/// help.
+pub fn help_text(binding: &Binding) -> String {
+    let mut lines = vec!["Controls:".to_owned()];
+    for axis in &Axis::ALL {
+        lines.push(format!(
+            "{}/{}: {}",
+            inputs_text(binding, &InputEffect::Axis(*axis, true)),
+            inputs_text(binding, &InputEffect::Axis(*axis, false)),
+            axis.description()
+        ));
+    }
+    for button in &Button::ALL {
+        let inputs = inputs_text(binding, &InputEffect::Button(*button));
+        lines.push(format!("{}: {}", inputs, button.description()));
+    }
+    lines.join("\n")
+}
+
+/// Loads the player's controls from the user config directory. A broken
+/// file falls back to the default bindings, reporting what's wrong with it,
+/// and a missing one is written out with the defaults to make editing easy.
+pub fn load_controls(ctx: &Context) -> Binding {
+    let path = ggez::filesystem::user_config_dir(ctx).join(CONTROLS_FILE);
+    let binding = if path.exists() {
+        File::open(&path)
+            .map_err(GameError::from)
+            .and_then(|file| load_input_binding(BufReader::new(file)))
+    } else {
+        let binding = create_input_binding();
+        save_controls(ctx, &binding).map(|_| binding)
+    };
+
+    binding.unwrap_or_else(|err| {
+        eprintln!("{}: {}, using the default controls", path.display(), err);
+        create_input_binding()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(text: &str) -> GameResult<Binding> {
+        load_input_binding(text.as_bytes())
+    }
+
+    fn inputs(binding: &Binding, button: Button) -> HashSet<Chord> {
+        binding.inputs_for(&InputEffect::Button(button)).into_iter().collect()
+    }
+
+    fn key(key: KeyCode) -> Chord {
+        Chord::from(InputType::KeyEvent(key))
+    }
+
+    #[test]
+    fn saved_controls_load_the_same() {
+        let mut binding = create_input_binding();
+        binding.unbind(&InputEffect::Button(Button::Reload));
+        binding.bind(
+            Chord::new(KeyMods::CTRL | KeyMods::SHIFT, InputType::KeyEvent(KeyCode::R)),
+            InputEffect::Button(Button::Reload),
+        );
+        let mut saved = Vec::new();
+        save_input_binding(&binding, &mut saved).unwrap();
+
+        let loaded = load_input_binding(&saved[..]).unwrap();
+        for action in actions() {
+            let expected: HashSet<Chord> = binding.inputs_for(&action).into_iter().collect();
+            let actual: HashSet<Chord> = loaded.inputs_for(&action).into_iter().collect();
+            assert_eq!(expected, actual, "{}", action_name(&action));
+        }
+    }
+
+    #[test]
+    fn v1_files_move_escape_to_pause() {
+        let binding = load("# voxelspace controls v1\nQuit = Escape\n").unwrap();
+        let ctrl_q = Chord::new(KeyMods::CTRL, InputType::KeyEvent(KeyCode::Q));
+        assert_eq!(inputs(&binding, Button::Quit), vec![ctrl_q].into_iter().collect());
+        assert_eq!(
+            inputs(&binding, Button::Pause),
+            vec![key(KeyCode::Escape)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn v1_upgrade_keeps_rebound_keys() {
+        let binding = load("# voxelspace controls v1\nQuit = Escape\nNext = Ctrl+Q\n").unwrap();
+        assert!(inputs(&binding, Button::Quit).is_empty());
+        assert_eq!(
+            inputs(&binding, Button::Pause),
+            vec![key(KeyCode::Escape)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn v2_files_are_not_upgraded() {
+        let binding = load("# voxelspace controls v2\nQuit = Escape\n").unwrap();
+        assert_eq!(
+            inputs(&binding, Button::Quit),
+            vec![key(KeyCode::Escape)].into_iter().collect()
+        );
+        assert!(inputs(&binding, Button::Pause).is_empty());
+    }
+
+    #[test]
+    fn missing_actions_keep_unclaimed_defaults() {
+        let binding = load("# voxelspace controls v2\nNext = B\n").unwrap();
+        assert_eq!(inputs(&binding, Button::Next), vec![key(KeyCode::B)].into_iter().collect());
+        assert!(inputs(&binding, Button::NextBookmark).is_empty());
+        assert_eq!(inputs(&binding, Button::Prev), vec![key(KeyCode::P)].into_iter().collect());
+    }
+
+    #[test]
+    fn empty_lists_unbind() {
+        let binding = load("# voxelspace controls v2\nReload =\n").unwrap();
+        assert!(inputs(&binding, Button::Reload).is_empty());
+    }
+
+    #[test]
+    fn bad_lines_are_errors() {
+        assert!(load("Next N").is_err());
+        assert!(load("Launch = N").is_err());
+        assert!(load("Next = Hyper+N").is_err());
+        assert!(load("Next = N\nnext = P").is_err());
+        assert!(load("Next = N\nPrev = N").is_err());
+        assert!(load("# voxelspace controls v3\n").is_err());
+        assert!(load("# voxelspace controls vX\n").is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use ggez::event;
 
+pub mod controls;
 pub mod types;
 
 use types::*;
@@ -8,10 +9,36 @@ use types::*;
 pub enum Button {
     Next,
     Prev,
+    /// Skips ten maps ahead
+    JumpNext,
+    /// Skips ten maps back
+    JumpPrev,
     Reload,
     Quit,
+    /// Opens the pause menu
+    Pause,
     ToggleDebug,
     Grab,
+    SaveDepth,
+    Teleport,
+    GroundFollow,
+    Movement,
+    RecordPath,
+    PlayPath,
+    Capture,
+    Screenshot,
+    Controls,
+    MouseLook,
+    /// Opens the map browser
+    Maps,
+    /// Opens the developer console
+    Console,
+    /// Bookmarks the current view
+    SaveBookmark,
+    /// Jumps to the next bookmark on the map
+    NextBookmark,
+    /// Jumps to the previous bookmark on the map
+    PrevBookmark,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -22,15 +49,123 @@ pub enum Axis {
     ViewDistance,
     Height,
     Fov,
+    /// Turns the camera, for analog sticks
+    Turn,
+    /// Looks up and down, for analog sticks
+    Look,
+}
+
+impl Button {
+    pub const ALL: [Button; 24] = [
+        Button::Next,
+        Button::Prev,
+        Button::JumpNext,
+        Button::JumpPrev,
+        Button::Reload,
+        Button::Quit,
+        Button::Pause,
+        Button::ToggleDebug,
+        Button::Grab,
+        Button::SaveDepth,
+        Button::Teleport,
+        Button::GroundFollow,
+        Button::Movement,
+        Button::RecordPath,
+        Button::PlayPath,
+        Button::Capture,
+        Button::Screenshot,
+        Button::Controls,
+        Button::MouseLook,
+        Button::Maps,
+        Button::Console,
+        Button::SaveBookmark,
+        Button::NextBookmark,
+        Button::PrevBookmark,
+    ];
+
+    /// What the button does, for help text and the controls menu.
+    pub fn description(self) -> &'static str {
+        match self {
+            Button::Next => "Next Map",
+            Button::Prev => "Previous Map",
+            Button::JumpNext => "Jump Ten Maps Forward",
+            Button::JumpPrev => "Jump Ten Maps Back",
+            Button::Reload => "Reload Map",
+            Button::Quit => "Quit",
+            Button::Pause => "Pause Menu",
+            Button::ToggleDebug => "Toggle Debug Text",
+            Button::Grab => "Drag to Pan/Rotate",
+            Button::SaveDepth => "Save Depth Map",
+            Button::Teleport => "Teleport to Cursor",
+            Button::GroundFollow => "Toggle Ground Follow",
+            Button::Movement => "Switch Fly/Walk/Helicopter",
+            Button::RecordPath => "Record Camera Path",
+            Button::PlayPath => "Play Camera Path",
+            Button::Capture => "Capture Frames",
+            Button::Screenshot => "Screenshot",
+            Button::Controls => "Controls",
+            Button::MouseLook => "Toggle Mouse Look",
+            Button::Maps => "Browse Maps",
+            Button::Console => "Developer Console",
+            Button::SaveBookmark => "Bookmark View",
+            Button::NextBookmark => "Next Bookmark",
+            Button::PrevBookmark => "Previous Bookmark",
+        }
+    }
+}
+
+impl Axis {
+    pub const ALL: [Axis; 8] = [
+        Axis::Throttle,
+        Axis::Strafe,
+        Axis::HeightScale,
+        Axis::ViewDistance,
+        Axis::Height,
+        Axis::Fov,
+        Axis::Turn,
+        Axis::Look,
+    ];
+
+    /// What the positive and negative directions do, for help text and the
+    /// controls menu.
+    pub fn description(self) -> &'static str {
+        match self {
+            Axis::Throttle => "Move Forward/Back",
+            Axis::Strafe => "Strafe Right/Left",
+            Axis::HeightScale => "Change Height Scale",
+            Axis::ViewDistance => "Change Render Distance",
+            Axis::Height => "Move Up/Down, Jump",
+            Axis::Fov => "Change FOV",
+            Axis::Turn => "Turn Right/Left",
+            Axis::Look => "Look Up/Down",
+        }
+    }
 }
 
 pub type Binding = InputBinding<Axis, Button>;
 pub type Event = InputEffect<Axis, Button>;
 pub type State = InputState<Axis, Button>;
 
+/// Create the input state, with movement that ramps up and stops quickly
+/// and tuning axes that start slowly for fine adjustments.
+pub fn create_input_state() -> State {
+    let movement = AxisResponse { sensitivity: 5.0, gravity: 5.0, snap: true };
+    let tuning = AxisResponse { sensitivity: 1.5, gravity: 8.0, snap: true };
+    InputState::new()
+        .with_axis_response(Axis::Throttle, movement)
+        .with_axis_response(Axis::Strafe, movement)
+        .with_axis_response(Axis::Height, movement)
+        .with_axis_response(Axis::HeightScale, tuning)
+        .with_axis_response(Axis::ViewDistance, tuning)
+        .with_axis_response(Axis::Fov, tuning)
+}
+
 /// Create the default keybindings for our input state.
 pub fn create_input_binding() -> InputBinding<Axis, Button> {
+    use event::Axis as PadAxis;
+    use event::Button as PadButton;
     use event::KeyCode;
+    use event::KeyMods;
     use event::MouseButton;
     InputBinding::new()
         .bind_key_to_axis(KeyCode::D, Axis::Strafe, true)
@@ -46,9 +181,45 @@ pub fn create_input_binding() -> InputBinding<Axis, Button> {
         .bind_key_to_axis(KeyCode::RBracket, Axis::Fov, true)
         .bind_key_to_axis(KeyCode::LBracket, Axis::Fov, false)
         .bind_mouse_to_button(MouseButton::Left, Button::Grab)
+        .bind_mouse_to_button(MouseButton::Right, Button::Teleport)
+        .bind_mouse_wheel_to_axis(WheelDirection::Up, Axis::Fov, false)
+        .bind_mouse_wheel_to_axis(WheelDirection::Down, Axis::Fov, true)
         .bind_key_to_button(KeyCode::N, Button::Next)
         .bind_key_to_button(KeyCode::P, Button::Prev)
+        .bind_chord_to_button(KeyMods::SHIFT, KeyCode::N, Button::JumpNext)
+        .bind_chord_to_button(KeyMods::SHIFT, KeyCode::P, Button::JumpPrev)
         .bind_key_to_button(KeyCode::R, Button::Reload)
         .bind_key_to_button(KeyCode::L, Button::ToggleDebug)
-        .bind_key_to_button(KeyCode::Escape, Button::Quit)
+        .bind_key_to_button(KeyCode::G, Button::GroundFollow)
+        .bind_key_to_button(KeyCode::M, Button::Movement)
+        .bind_key_to_button(KeyCode::Tab, Button::MouseLook)
+        .bind_key_to_button(KeyCode::F1, Button::Controls)
+        .bind_key_to_button(KeyCode::F2, Button::Maps)
+        .bind_key_to_button(KeyCode::Grave, Button::Console)
+        .bind_chord_to_button(KeyMods::CTRL, KeyCode::B, Button::SaveBookmark)
+        .bind_key_to_button(KeyCode::B, Button::NextBookmark)
+        .bind_key_to_button(KeyCode::V, Button::PrevBookmark)
+        .bind_key_to_button(KeyCode::F5, Button::RecordPath)
+        .bind_key_to_button(KeyCode::F6, Button::PlayPath)
+        .bind_key_to_button(KeyCode::F7, Button::Capture)
+        .bind_key_to_button(KeyCode::F9, Button::SaveDepth)
+        .bind_key_to_button(KeyCode::F12, Button::Screenshot)
+        .bind_chord_to_button(KeyMods::CTRL, KeyCode::S, Button::Screenshot)
+        .bind_key_to_button(KeyCode::Escape, Button::Pause)
+        .bind_chord_to_button(KeyMods::CTRL, KeyCode::Q, Button::Quit)
+        .bind_gamepad_axis_to_axis(PadAxis::LeftStickY, Axis::Throttle, true)
+        .bind_gamepad_axis_to_axis(PadAxis::LeftStickX, Axis::Strafe, true)
+        .bind_gamepad_axis_to_axis(PadAxis::RightStickX, Axis::Turn, true)
+        .bind_gamepad_axis_to_axis(PadAxis::RightStickY, Axis::Look, true)
+        .bind_gamepad_axis_to_axis(PadAxis::RightZ, Axis::Height, true)
+        .bind_gamepad_axis_to_axis(PadAxis::LeftZ, Axis::Height, false)
+        .bind_gamepad_button_to_axis(PadButton::RightTrigger2, Axis::Height, true)
+        .bind_gamepad_button_to_axis(PadButton::LeftTrigger2, Axis::Height, false)
+        .bind_gamepad_button_to_axis(PadButton::DPadUp, Axis::HeightScale, true)
+        .bind_gamepad_button_to_axis(PadButton::DPadDown, Axis::HeightScale, false)
+        .bind_gamepad_button_to_axis(PadButton::DPadRight, Axis::ViewDistance, true)
+        .bind_gamepad_button_to_axis(PadButton::DPadLeft, Axis::ViewDistance, false)
+        .bind_gamepad_button(PadButton::North, Button::Movement)
+        .bind_gamepad_button(PadButton::West, Button::GroundFollow)
+        .bind_gamepad_button(PadButton::Select, Button::ToggleDebug)
 }
@@ -5,27 +5,33 @@
 //! The goals are:
 //!
 //! * Have a layer of abstract key bindings rather than
-//! looking at concrete event types
+//!   looking at concrete event types
 //! * Use this to be able to abstract away differences
-//! between keyboards, joysticks and game controllers
-//! (rather based on Unity3D),
-//! * Do some tweening of input axes and stuff just for
-//! fun.
+//!   between keyboards, joysticks and game controllers
+//!   (rather based on Unity3D),
+//! * Tween input axes, so digital inputs ramp up and
+//!   settle back down over time like analog ones.
 //! * Take ggez's event-based input API, and present event- or
-//! state-based API so you can do whichever you want.
+//!   state-based API so you can do whichever you want.
 
 // https://github.com/ggez/ggez-goodies
 
 use std::collections::HashMap;
 use std::hash::Hash;
 
+use ggez::event::Axis as GamepadAxis;
+use ggez::event::Button as GamepadButton;
 use ggez::event::KeyCode;
+use ggez::event::KeyMods;
 use ggez::event::MouseButton;
 
 // Okay, but how does it actually work?
 // Basically we have to bind input events to buttons and axes.
-// Input events can be keys, mouse buttons/motion, or eventually
-// joystick/controller inputs.  Mouse delta can be mapped to axes too.
+// Input events can be keys, mouse buttons/motion, or gamepad
+// buttons and sticks.  Sticks feed their analog value into axes.
+// Keys and buttons only set where an axis is heading, and `update`
+// moves it there at the axis' sensitivity, or back to zero at its
+// gravity once released.
 //
 // https://docs.unity3d.com/Manual/ConventionalGameInput.html has useful
 // descriptions of the exact behavior of axes.
@@ -42,10 +48,59 @@ use ggez::event::MouseButton;
 //
 // Easy way?  Hash map of event -> axis/button bindings.
 
+const DEFAULT_AXIS_SENSITIVITY: f32 = 3.0;
+const DEFAULT_AXIS_GRAVITY: f32 = 3.0;
+const DEFAULT_DEADZONE: f32 = 0.15;
+const DEFAULT_RESPONSE_CURVE: f32 = 1.5;
+/// How far an analog input has to be pushed to count as pressing a button
+pub const ANALOG_PRESS_THRESHOLD: f32 = 0.5;
+/// Seconds a mouse wheel notch holds an axis at full, so scrolling moves
+/// it in small steps
+const WHEEL_NOTCH_TIME: f32 = 0.1;
+
+/// Which way the mouse wheel was turned.
 #[derive(Debug, Hash, Eq, PartialEq, Copy, Clone)]
-enum InputType {
+pub enum WheelDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Hash, Eq, PartialEq, Copy, Clone)]
+pub enum InputType {
     KeyEvent(KeyCode),
     MouseButtonEvent(MouseButton),
+    GamepadButtonEvent(GamepadButton),
+    /// An analog stick or trigger, which can drive an axis smoothly
+    GamepadAxisEvent(GamepadAxis),
+    /// A turn of the mouse wheel, which nudges an axis or taps a button
+    MouseWheelEvent(WheelDirection),
+}
+
+/// A physical input along with the modifier keys that have to be held for
+/// it, like Ctrl+S. Inputs without modifiers convert straight into chords.
+#[derive(Debug, Hash, Eq, PartialEq, Copy, Clone)]
+pub struct Chord {
+    pub mods: KeyMods,
+    pub input: InputType,
+}
+
+impl Chord {
+    pub fn new(mods: KeyMods, input: InputType) -> Self {
+        Chord { mods, input }
+    }
+
+    /// How many modifiers the chord needs, more specific chords win.
+    fn specificity(&self) -> u32 {
+        self.mods.bits().count_ones()
+    }
+}
+
+impl From<InputType> for Chord {
+    fn from(input: InputType) -> Self {
+        Chord::new(KeyMods::NONE, input)
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -61,15 +116,109 @@ where
 
 #[derive(Debug, Copy, Clone)]
 struct AxisState {
-    // Where the axis is moving towards.  Possible
-    // values are -1, 0, +1, or a continuous range
-    // for analog devices
+    // Where the keys and buttons are moving the axis
+    // towards.  Possible values are -1, 0, +1
     direction: f32,
+    // Where the keys and buttons actually have the
+    // axis, following direction at the speed set by
+    // its AxisResponse
+    position: f32,
+    // Where analog inputs bound to the positive and
+    // negative directions hold the axis.  They're
+    // smooth already, so they skip the AxisResponse,
+    // and kept apart from the keys so stick noise
+    // doesn't cancel a held key
+    analog_positive: f32,
+    analog_negative: f32,
+    // Seconds of full deflection the mouse wheel still
+    // has to apply, negative for the other direction
+    wheel: f32,
+    // How much of the wheel was applied this frame
+    wheel_position: f32,
 }
 
 impl Default for AxisState {
     fn default() -> Self {
-        AxisState { direction: 0.0 }
+        AxisState {
+            direction: 0.0,
+            position: 0.0,
+            analog_positive: 0.0,
+            analog_negative: 0.0,
+            wheel: 0.0,
+            wheel_position: 0.0,
+        }
+    }
+}
+
+impl AxisState {
+    fn step(&mut self, response: &AxisResponse, dt: f32) {
+        let spent = self.wheel.clamp(-dt, dt);
+        self.wheel -= spent;
+        self.wheel_position = if dt > 0.0 { spent / dt } else { 0.0 };
+
+        if response.snap && self.direction * self.position < 0.0 {
+            self.position = 0.0;
+        }
+        let rate = if self.direction == 0.0 { response.gravity } else { response.sensitivity };
+        let max_change = rate * dt;
+        self.position += (self.direction - self.position).clamp(-max_change, max_change);
+    }
+
+    /// Combines a value from the keys with the analog inputs, whichever is
+    /// pushed further winning.
+    fn combine(&self, keys: f32) -> f32 {
+        let analog = (self.analog_positive + self.analog_negative).clamp(-1.0, 1.0);
+        if analog.abs() > keys.abs() {
+            analog
+        } else {
+            keys
+        }
+    }
+}
+
+/// How quickly a digital axis moves, in the style of Unity's input manager.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AxisResponse {
+    /// Units per second the axis moves towards a pressed direction
+    pub sensitivity: f32,
+    /// Units per second the axis falls back to zero once released
+    pub gravity: f32,
+    /// Jump to zero first when the opposite direction is pressed
+    pub snap: bool,
+}
+
+impl Default for AxisResponse {
+    fn default() -> Self {
+        AxisResponse {
+            sensitivity: DEFAULT_AXIS_SENSITIVITY,
+            gravity: DEFAULT_AXIS_GRAVITY,
+            snap: true,
+        }
+    }
+}
+
+/// Shapes raw analog values before they reach the axes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AnalogResponse {
+    /// Values closer to zero than this are ignored, so a worn stick
+    /// doesn't drift
+    pub deadzone: f32,
+    /// Exponent applied to the value past the deadzone. Higher values give
+    /// finer control near the center
+    pub response_curve: f32,
+}
+
+impl Default for AnalogResponse {
+    fn default() -> Self {
+        AnalogResponse { deadzone: DEFAULT_DEADZONE, response_curve: DEFAULT_RESPONSE_CURVE }
+    }
+}
+
+impl AnalogResponse {
+    pub fn apply(&self, value: f32) -> f32 {
+        let live_range = (1.0 - self.deadzone).max(f32::EPSILON);
+        let magnitude = ((value.abs() - self.deadzone) / live_range).clamp(0.0, 1.0);
+        magnitude.powf(self.response_curve).copysign(value)
     }
 }
 
@@ -79,9 +228,9 @@ struct ButtonState {
     pressed_last_frame: bool,
 }
 
-/// A struct that contains a mapping from physical input events
-/// (currently just `KeyCode`s) to whatever your logical Axis/Button
-/// types are.
+/// A struct that contains a mapping from physical input events,
+/// optionally chorded with modifier keys, to whatever your logical
+/// Axis/Button types are.
 pub struct InputBinding<Axes, Buttons>
 where
     Axes: Hash + Eq + Clone,
@@ -90,7 +239,7 @@ where
     // Once EnumSet is stable it should be used for these
     // instead of BTreeMap. ♥?
     // Binding of keys to input values.
-    bindings: HashMap<InputType, InputEffect<Axes, Buttons>>,
+    bindings: HashMap<Chord, InputEffect<Axes, Buttons>>,
 }
 
 impl<Axes, Buttons> Default for InputBinding<Axes, Buttons>
@@ -114,31 +263,131 @@ where
     /// Adds a key binding connecting the given keycode to the given
     /// logical axis.
     pub fn bind_key_to_axis(mut self, keycode: KeyCode, axis: Axes, positive: bool) -> Self {
-        self.bindings.insert(InputType::KeyEvent(keycode), InputEffect::Axis(axis, positive));
+        self.bindings
+            .insert(InputType::KeyEvent(keycode).into(), InputEffect::Axis(axis, positive));
         self
     }
 
     /// Adds a key binding connecting the given keycode to the given
     /// logical button.
     pub fn bind_key_to_button(mut self, keycode: KeyCode, button: Buttons) -> Self {
-        self.bindings.insert(InputType::KeyEvent(keycode), InputEffect::Button(button));
+        self.bindings.insert(InputType::KeyEvent(keycode).into(), InputEffect::Button(button));
+        self
+    }
+
+    /// Adds a key binding that only applies while the given modifiers are
+    /// held, like Ctrl+S.
+    pub fn bind_chord_to_button(
+        mut self,
+        mods: KeyMods,
+        keycode: KeyCode,
+        button: Buttons,
+    ) -> Self {
+        let chord = Chord::new(mods, InputType::KeyEvent(keycode));
+        self.bindings.insert(chord, InputEffect::Button(button));
         self
     }
 
     pub fn bind_mouse_to_button(mut self, mouse_button: MouseButton, button: Buttons) -> Self {
         self.bindings
-            .insert(InputType::MouseButtonEvent(mouse_button), InputEffect::Button(button));
+            .insert(InputType::MouseButtonEvent(mouse_button).into(), InputEffect::Button(button));
         self
     }
 
+    /// Adds a binding connecting a direction of the mouse wheel to the
+    /// given logical axis. Each notch moves the axis for a moment.
+    pub fn bind_mouse_wheel_to_axis(
+        mut self,
+        direction: WheelDirection,
+        axis: Axes,
+        positive: bool,
+    ) -> Self {
+        self.bindings.insert(
+            InputType::MouseWheelEvent(direction).into(),
+            InputEffect::Axis(axis, positive),
+        );
+        self
+    }
+
+    pub fn bind_gamepad_button(mut self, pad_button: GamepadButton, button: Buttons) -> Self {
+        self.bindings
+            .insert(InputType::GamepadButtonEvent(pad_button).into(), InputEffect::Button(button));
+        self
+    }
+
+    pub fn bind_gamepad_button_to_axis(
+        mut self,
+        pad_button: GamepadButton,
+        axis: Axes,
+        positive: bool,
+    ) -> Self {
+        self.bindings.insert(
+            InputType::GamepadButtonEvent(pad_button).into(),
+            InputEffect::Axis(axis, positive),
+        );
+        self
+    }
+
+    /// Adds a binding connecting an analog stick or trigger to the given
+    /// logical axis. With `positive` unset the axis moves against the
+    /// stick.
+    pub fn bind_gamepad_axis_to_axis(
+        mut self,
+        pad_axis: GamepadAxis,
+        axis: Axes,
+        positive: bool,
+    ) -> Self {
+        self.bindings.insert(
+            InputType::GamepadAxisEvent(pad_axis).into(),
+            InputEffect::Axis(axis, positive),
+        );
+        self
+    }
+
+    /// Adds a binding connecting any chord to the given effect, replacing
+    /// whatever that chord was bound to before.
+    pub fn bind(&mut self, chord: Chord, effect: InputEffect<Axes, Buttons>) {
+        self.bindings.insert(chord, effect);
+    }
+
+    /// Removes every binding to the given effect.
+    pub fn unbind(&mut self, effect: &InputEffect<Axes, Buttons>) {
+        self.bindings.retain(|_, bound| bound != effect);
+    }
+
+    /// All chords bound to the given effect.
+    pub fn inputs_for(&self, effect: &InputEffect<Axes, Buttons>) -> Vec<Chord> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == effect)
+            .map(|(chord, _)| *chord)
+            .collect()
+    }
+
+    /// The effect the given chord is bound to exactly, if any.
+    pub fn bound_to(&self, chord: Chord) -> Option<InputEffect<Axes, Buttons>> {
+        self.bindings.get(&chord).cloned()
+    }
+
+    /// The effect of the given physical input while `mods` are held. Of the
+    /// chords whose modifiers are all held, the one needing the most wins,
+    /// so Ctrl+S beats a plain S.
+    pub fn resolve(&self, input: InputType, mods: KeyMods) -> Option<InputEffect<Axes, Buttons>> {
+        self.bindings
+            .iter()
+            .filter(|(chord, _)| chord.input == input && mods.contains(chord.mods))
+            .max_by_key(|(chord, _)| chord.specificity())
+            .map(|(_, effect)| effect.clone())
+    }
+
     /// Takes an physical input type and turns it into a logical input type (keycode -> axis/button).
     pub fn resolve_keycode(&self, keycode: KeyCode) -> Option<InputEffect<Axes, Buttons>> {
-        self.bindings.get(&InputType::KeyEvent(keycode)).cloned()
+        self.bound_to(InputType::KeyEvent(keycode).into())
     }
 
     /// Takes an physical input type and turns it into a logical input type (keycode -> axis/button).
     pub fn resolve_mousebutton(&self, button: MouseButton) -> Option<InputEffect<Axes, Buttons>> {
-        self.bindings.get(&InputType::MouseButtonEvent(button)).cloned()
+        self.bound_to(InputType::MouseButtonEvent(button).into())
     }
 
     pub fn resolve_mousemotion(
@@ -162,6 +411,16 @@ where
     axes: HashMap<Axes, AxisState>,
     // Input states for buttons
     buttons: HashMap<Buttons, ButtonState>,
+    // Physical mouse buttons, whatever they're bound to
+    mouse_buttons: HashMap<MouseButton, ButtonState>,
+    // Mouse wheel movement since the last update
+    scroll: (f32, f32),
+    // Where the pointer last moved to
+    pointer: (f32, f32),
+    // How each axis ramps up and down
+    responses: HashMap<Axes, AxisResponse>,
+    pub default_response: AxisResponse,
+    pub analog: AnalogResponse,
 }
 
 impl<Axes, Buttons> Default for InputState<Axes, Buttons>
@@ -170,7 +429,16 @@ where
     Buttons: Eq + Hash + Clone,
 {
     fn default() -> Self {
-        InputState { axes: HashMap::new(), buttons: HashMap::new() }
+        InputState {
+            axes: HashMap::new(),
+            buttons: HashMap::new(),
+            mouse_buttons: HashMap::new(),
+            scroll: (0.0, 0.0),
+            pointer: (0.0, 0.0),
+            responses: HashMap::new(),
+            default_response: AxisResponse::default(),
+            analog: AnalogResponse::default(),
+        }
     }
 }
 
@@ -184,11 +452,31 @@ where
     }
     /// Updates the logical input state based on the actual
     /// physical input state.  Should be called in your update()
-    /// handler.
-    pub fn update(&mut self, _dt: f32) {
+    /// handler, after the game has looked at the input for the frame.
+    pub fn update(&mut self, dt: f32) {
         for (_button, button_status) in self.buttons.iter_mut() {
             button_status.pressed_last_frame = button_status.pressed;
         }
+
+        for (_button, button_status) in self.mouse_buttons.iter_mut() {
+            button_status.pressed_last_frame = button_status.pressed;
+        }
+        self.scroll = (0.0, 0.0);
+
+        for (axis, axis_status) in self.axes.iter_mut() {
+            let response = self.responses.get(axis).unwrap_or(&self.default_response);
+            axis_status.step(response, dt);
+        }
+    }
+
+    /// Sets how quickly the given axis moves, instead of `default_response`.
+    pub fn set_axis_response(&mut self, axis: Axes, response: AxisResponse) {
+        self.responses.insert(axis, response);
+    }
+
+    pub fn with_axis_response(mut self, axis: Axes, response: AxisResponse) -> Self {
+        self.set_axis_response(axis, response);
+        self
     }
 
     /// Takes an InputEffect and actually applies it.
@@ -211,14 +499,81 @@ where
                 let button_status = self.buttons.entry(button).or_insert_with(f);
                 button_status.pressed = started;
             }
-            _ => (),
+            InputEffect::Pointer(x, y, _dx, _dy) => self.pointer = (x, y),
+        }
+    }
+
+    /// Takes a raw analog value for an input bound to `effect`. Axes follow
+    /// the value after it's been through `analog`, and buttons are pressed
+    /// once it's pushed far enough. Returns the effect and whether it
+    /// started, if anything changed that scenes should hear about.
+    pub fn update_analog(
+        &mut self,
+        effect: InputEffect<Axes, Buttons>,
+        value: f32,
+    ) -> Option<(InputEffect<Axes, Buttons>, bool)> {
+        match effect.clone() {
+            InputEffect::Axis(axis, positive) => {
+                let value = self.analog.apply(value);
+                let f = || AxisState::default();
+                let axis_status = self.axes.entry(axis).or_insert_with(f);
+                if positive {
+                    axis_status.analog_positive = value;
+                } else {
+                    axis_status.analog_negative = -value;
+                }
+                Some((effect, value != 0.0))
+            }
+            InputEffect::Button(button) => {
+                let pressed = value.abs() >= ANALOG_PRESS_THRESHOLD;
+                if pressed == self.get_button_down(button.clone()) {
+                    return None;
+                }
+                self.update_effect(effect.clone(), pressed);
+                Some((effect, pressed))
+            }
+            InputEffect::Pointer(..) => None,
+        }
+    }
+
+    /// Records the physical state of a mouse button, whether or not it's
+    /// bound to anything.
+    pub fn update_mouse_button(&mut self, button: MouseButton, pressed: bool) {
+        let f = || ButtonState::default();
+        let button_status = self.mouse_buttons.entry(button).or_insert_with(f);
+        button_status.pressed = pressed;
+    }
+
+    /// Adds mouse wheel movement, in notches, to the delta for this frame.
+    pub fn update_scroll(&mut self, x: f32, y: f32) {
+        self.scroll.0 += x;
+        self.scroll.1 += y;
+    }
+
+    /// Takes `notches` of mouse wheel movement for an input bound to
+    /// `effect`. Axes are held at full for a moment per notch, buttons
+    /// aren't held at all and only hear about it through events.
+    pub fn update_wheel(&mut self, effect: InputEffect<Axes, Buttons>, notches: f32) {
+        if let InputEffect::Axis(axis, positive) = effect {
+            let f = || AxisState::default();
+            let axis_status = self.axes.entry(axis).or_insert_with(f);
+            let time = notches * WHEEL_NOTCH_TIME;
+            axis_status.wheel += if positive { time } else { -time };
         }
     }
 
+    /// Smoothed value of the axis, between -1 and 1.
     pub fn get_axis(&self, axis: Axes) -> f32 {
         let d = AxisState::default();
         let axis_status = self.axes.get(&axis).unwrap_or(&d);
-        axis_status.direction
+        (axis_status.combine(axis_status.position) + axis_status.wheel_position).clamp(-1.0, 1.0)
+    }
+
+    /// Value the axis is heading towards, without any smoothing.
+    pub fn get_axis_raw(&self, axis: Axes) -> f32 {
+        let d = AxisState::default();
+        let axis_status = self.axes.get(&axis).unwrap_or(&d);
+        (axis_status.combine(axis_status.direction) + axis_status.wheel_position).clamp(-1.0, 1.0)
     }
 
     fn get_button(&self, button: Buttons) -> ButtonState {
@@ -250,30 +605,117 @@ where
         !b.pressed && b.pressed_last_frame
     }
 
-    pub fn mouse_scroll_delta(&self) {
-        unimplemented!()
+    /// How far the mouse wheel was turned this frame, sideways and up, in
+    /// notches.
+    pub fn mouse_scroll_delta(&self) -> (f32, f32) {
+        self.scroll
+    }
+
+    /// Where the mouse pointer last moved to, in screen coordinates. This
+    /// follows the recorded input, so unlike asking ggez it's the same in a
+    /// replay.
+    pub fn mouse_position(&self) -> (f32, f32) {
+        self.pointer
     }
 
-    pub fn get_mouse_button(&self) {
-        unimplemented!()
+    fn get_mouse_button_state(&self, button: MouseButton) -> ButtonState {
+        let d = ButtonState::default();
+        let button_status = self.mouse_buttons.get(&button).unwrap_or(&d);
+        *button_status
     }
 
-    pub fn get_mouse_button_down(&self) {
-        unimplemented!()
+    /// Returns whether the mouse button is held down.
+    ///
+    /// Like Unity, and unlike the logical buttons, the `_down` and `_up`
+    /// versions are edge triggered: they're only true on the frame the
+    /// button was pressed or released.
+    pub fn get_mouse_button(&self, button: MouseButton) -> bool {
+        self.get_mouse_button_state(button).pressed
     }
 
-    pub fn get_mouse_button_up(&self) {
-        unimplemented!()
+    pub fn get_mouse_button_down(&self, button: MouseButton) -> bool {
+        let b = self.get_mouse_button_state(button);
+        b.pressed && !b.pressed_last_frame
+    }
+
+    pub fn get_mouse_button_up(&self, button: MouseButton) -> bool {
+        let b = self.get_mouse_button_state(button);
+        !b.pressed && b.pressed_last_frame
     }
 
     pub fn reset_input_state(&mut self) {
         for (_axis, axis_status) in self.axes.iter_mut() {
             axis_status.direction = 0.0;
+            axis_status.position = 0.0;
+            axis_status.analog_positive = 0.0;
+            axis_status.analog_negative = 0.0;
+            axis_status.wheel = 0.0;
+            axis_status.wheel_position = 0.0;
         }
 
         for (_button, button_status) in self.buttons.iter_mut() {
             button_status.pressed = false;
             button_status.pressed_last_frame = false;
         }
+
+        for (_button, button_status) in self.mouse_buttons.iter_mut() {
+            button_status.pressed = false;
+            button_status.pressed_last_frame = false;
+        }
+        self.scroll = (0.0, 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::input::create_input_binding;
+    use crate::game::input::Axis;
+    use crate::game::input::Button;
+
+    fn key(keycode: KeyCode) -> InputType {
+        InputType::KeyEvent(keycode)
+    }
+
+    #[test]
+    fn the_most_specific_chord_wins() {
+        let mut binding = InputBinding::new()
+            .bind_key_to_axis(KeyCode::S, Axis::Throttle, false)
+            .bind_chord_to_button(KeyMods::CTRL, KeyCode::S, Button::Screenshot);
+        let throttle = Some(InputEffect::Axis(Axis::Throttle, false));
+        let screenshot = Some(InputEffect::Button(Button::Screenshot));
+        assert_eq!(binding.resolve(key(KeyCode::S), KeyMods::NONE), throttle);
+        assert_eq!(binding.resolve(key(KeyCode::S), KeyMods::CTRL), screenshot);
+        assert_eq!(binding.resolve(key(KeyCode::S), KeyMods::CTRL | KeyMods::SHIFT), screenshot);
+
+        binding.bind(
+            Chord::new(KeyMods::CTRL | KeyMods::SHIFT, key(KeyCode::S)),
+            InputEffect::Button(Button::Quit),
+        );
+        assert_eq!(
+            binding.resolve(key(KeyCode::S), KeyMods::CTRL | KeyMods::SHIFT),
+            Some(InputEffect::Button(Button::Quit))
+        );
+        assert_eq!(binding.resolve(key(KeyCode::S), KeyMods::CTRL), screenshot);
+        assert_eq!(binding.resolve(key(KeyCode::D), KeyMods::CTRL), None);
+    }
+
+    #[test]
+    fn held_modifiers_dont_block_plain_keys() {
+        // shift is also the key for going down, so it's often held while
+        // flying forwards
+        let binding = create_input_binding();
+        assert_eq!(
+            binding.resolve(key(KeyCode::W), KeyMods::SHIFT),
+            Some(InputEffect::Axis(Axis::Throttle, true))
+        );
+        assert_eq!(
+            binding.resolve(key(KeyCode::N), KeyMods::SHIFT),
+            Some(InputEffect::Button(Button::JumpNext))
+        );
+        assert_eq!(
+            binding.resolve(key(KeyCode::N), KeyMods::NONE),
+            Some(InputEffect::Button(Button::Next))
+        );
     }
 }
@@ -1,76 +1,453 @@
+use std::any::Any;
+use std::collections::HashMap;
+
 use ggez::event;
+use ggez::event::KeyMods;
+use ggez::graphics;
 use ggez::graphics::Rect;
 use ggez::Context;
 use ggez::GameResult;
 
+pub mod console;
 pub mod input;
+pub mod replay;
 pub mod scene;
 
 pub use input::Event;
 pub use scene::Scene;
+pub use scene::Transition;
+
+use self::console::Commands;
+use self::input::types::InputType;
+use self::input::types::WheelDirection;
+use self::input::types::ANALOG_PRESS_THRESHOLD;
+use self::replay::InputRecorder;
+use self::replay::InputReplay;
+use self::replay::RecordedInput;
 
-use self::input::create_input_binding;
-use self::input::types::InputState;
+/// Updates per second
+pub const DEFAULT_TICK_RATE: f32 = 60.0;
+/// Slowest update rate the settings allow, past which the movement modes
+/// take steps too long to stay stable
+pub const MIN_TICK_RATE: f32 = 10.0;
+/// Fastest update rate the settings allow, past which a frame can't run
+/// enough updates to keep up
+pub const MAX_TICK_RATE: f32 = 1000.0;
+/// Updates a single frame can run to catch up, so a long stall doesn't
+/// turn into a longer one
+const MAX_UPDATES_PER_FRAME: f32 = 8.0;
 
 pub struct World {
     pub input: input::State,
+    pub binding: input::Binding,
+    /// Updates per second. Updates always cover the same time however fast
+    /// frames are drawn, so motion doesn't depend on the frame rate
+    pub tick_rate: f32,
+    /// How far the current frame is past the last update, as a fraction of
+    /// the timestep, for smoothing motion between updates
+    pub interpolation: f32,
+    /// Makes every frame cover this many seconds instead of the real time,
+    /// so slow frames don't show up as stutter in captured video
+    pub frame_time: Option<f32>,
+    /// Console commands the scenes understand, and the console's output
+    pub commands: Commands,
+}
+
+impl World {
+    pub fn new(binding: input::Binding) -> Self {
+        World {
+            input: input::create_input_state(),
+            binding,
+            tick_rate: DEFAULT_TICK_RATE,
+            interpolation: 0.0,
+            frame_time: None,
+            commands: Commands::default(),
+        }
+    }
+
+    /// Seconds each update covers.
+    pub fn timestep(&self) -> f32 {
+        1.0 / self.tick_rate
+    }
 }
 
 pub struct Game {
     scenes: Vec<Box<dyn Scene>>,
     world: World,
-    input_binding: input::Binding,
-    prev_x: f32,
-    prev_y: f32,
+    /// What each held input was resolved to when it was pressed, so letting
+    /// go of a modifier first still releases the chord
+    held: HashMap<InputType, input::Event>,
+    recorder: Option<InputRecorder>,
+    /// While set, inputs come from the log instead of ggez
+    replay: Option<InputReplay>,
+    /// Seconds of frame time not yet covered by an update
+    accumulator: f32,
 }
 
 impl Game {
-    pub fn new(scene_stack: Vec<Box<dyn Scene>>) -> Self {
-        Game {
-            scenes: scene_stack,
-            world: World { input: InputState::default() },
-            input_binding: create_input_binding(),
-            prev_x: f32::NAN,
-            prev_y: f32::NAN,
+    /// Starts the game with the given scenes, entering them from the bottom
+    /// of the stack up.
+    pub fn new(
+        ctx: &mut Context,
+        scene_stack: Vec<Box<dyn Scene>>,
+        mut world: World,
+    ) -> GameResult<Self> {
+        let mut scenes = Vec::with_capacity(scene_stack.len());
+        for mut scene in scene_stack {
+            scene.on_enter(&mut world, ctx)?;
+            scenes.push(scene);
+        }
+        Ok(Game {
+            scenes,
+            world,
+            held: HashMap::new(),
+            recorder: None,
+            replay: None,
+            accumulator: 0.0,
+        })
+    }
+
+    /// Carries out what a scene asked for.
+    fn transition(&mut self, ctx: &mut Context, transition: Transition) -> GameResult {
+        match transition {
+            Transition::None => (),
+            Transition::Push(scene) => {
+                if let Some(below) = self.scenes.last_mut() {
+                    below.on_pause(&mut self.world, ctx)?;
+                }
+                self.push(ctx, scene)?;
+            }
+            Transition::Pop => self.resume(ctx, None)?,
+            Transition::Return(result) => self.resume(ctx, Some(result))?,
+            Transition::Notify(message) => {
+                if let [.., below, _] = &mut self.scenes[..] {
+                    below.on_notify(&mut self.world, ctx, message)?;
+                }
+            }
+            Transition::Replace(scene) => {
+                self.pop(ctx)?;
+                self.push(ctx, scene)?;
+            }
+            Transition::Quit => self.exit_all(ctx)?,
+        }
+        if self.scenes.is_empty() {
+            event::quit(ctx);
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, ctx: &mut Context, mut scene: Box<dyn Scene>) -> GameResult {
+        scene.on_enter(&mut self.world, ctx)?;
+        self.scenes.push(scene);
+        Ok(())
+    }
+
+    fn pop(&mut self, ctx: &mut Context) -> GameResult {
+        if let Some(mut scene) = self.scenes.pop() {
+            scene.on_exit(&mut self.world, ctx)?;
+        }
+        Ok(())
+    }
+
+    /// Pops the top scene and hands its result to the one below.
+    fn resume(&mut self, ctx: &mut Context, result: Option<Box<dyn Any>>) -> GameResult {
+        self.pop(ctx)?;
+        if let Some(below) = self.scenes.last_mut() {
+            below.on_resume(&mut self.world, ctx, result)?;
+        }
+        Ok(())
+    }
+
+    /// Takes every scene off the stack, top first, without resuming the
+    /// ones underneath along the way.
+    fn exit_all(&mut self, ctx: &mut Context) -> GameResult {
+        while !self.scenes.is_empty() {
+            self.pop(ctx)?;
+        }
+        Ok(())
+    }
+
+    /// Logs every input from now on.
+    pub fn record_input(&mut self, recorder: InputRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Plays back a log, ignoring the real input until it's done.
+    pub fn replay_input(&mut self, replay: InputReplay) {
+        self.replay = Some(replay);
+    }
+
+    fn record(&mut self, recorded: RecordedInput) -> GameResult {
+        match &mut self.recorder {
+            Some(recorder) => recorder.record(recorded),
+            None => Ok(()),
+        }
+    }
+
+    /// Feeds a resolved input into the input state and the current scene,
+    /// whether it's live or replayed.
+    fn apply(&mut self, ctx: &mut Context, recorded: RecordedInput) -> GameResult {
+        self.record(recorded)?;
+        match recorded {
+            RecordedInput::Effect(ev, started) => {
+                self.world.input.update_effect(ev, started);
+                self.input(ctx, ev, started)?;
+            }
+            RecordedInput::Analog(effect, value) => {
+                if let Some((ev, started)) = self.world.input.update_analog(effect, value) {
+                    self.input(ctx, ev, started)?;
+                }
+            }
+            RecordedInput::Wheel(ev, notches) => {
+                self.world.input.update_wheel(ev, notches);
+                self.input(ctx, ev, true)?;
+                self.input(ctx, ev, false)?;
+            }
+            RecordedInput::Raw(input, started) => {
+                if let Some(scene) = self.scenes.last_mut() {
+                    scene.as_mut().raw_input(&mut self.world, ctx, input, started)?;
+                }
+            }
+            RecordedInput::Text(character) => {
+                if let Some(scene) = self.scenes.last_mut() {
+                    scene.as_mut().text_input(&mut self.world, ctx, character)?;
+                }
+            }
+            RecordedInput::MouseButton(button, pressed) => {
+                self.world.input.update_mouse_button(button, pressed);
+            }
+            RecordedInput::Scroll(x, y) => self.world.input.update_scroll(x, y),
+        }
+        Ok(())
+    }
+
+    /// Runs the lines submitted to the console since the last frame.
+    fn run_commands(&mut self, ctx: &mut Context) -> GameResult {
+        while let Some(line) = self.world.commands.next_pending() {
+            self.world.commands.print(&format!("> {}", line));
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (name, args) = match words.split_first() {
+                Some((name, args)) => (*name, args),
+                None => continue,
+            };
+            if self.world.commands.get(name).is_none() {
+                self.world.commands.print(&format!("unknown command {}, try help", name));
+                continue;
+            }
+
+            for scene in self.scenes.iter_mut().rev() {
+                if let Some(output) = scene.as_mut().command(&mut self.world, ctx, name, args)? {
+                    self.world.commands.print(&output);
+                    break;
+                }
+            }
         }
+        Ok(())
+    }
+
+    /// Offers a physical input to the current scene, returning whether the
+    /// scene used it up.
+    fn offer_raw(
+        &mut self,
+        ctx: &mut Context,
+        input: InputType,
+        started: bool,
+    ) -> GameResult<bool> {
+        if let Some(scene) = self.scenes.last_mut() {
+            if scene.as_mut().raw_input(&mut self.world, ctx, input, started)? {
+                self.record(RecordedInput::Raw(input, started))?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
     }
 
     fn input(&mut self, ctx: &mut Context, event: input::Event, started: bool) -> GameResult {
         if let Some(scene) = self.scenes.last_mut() {
-            scene.as_mut().input(&mut self.world, ctx, event, started)?;
+            let transition = scene.as_mut().input(&mut self.world, ctx, event, started)?;
+            self.transition(ctx, transition)?;
         } else {
             event::quit(ctx);
         }
         Ok(())
     }
+
+    /// Offers a key or mouse button to the current scene, then resolves it
+    /// through the bindings, with the modifiers currently held, if the
+    /// scene didn't use it.
+    fn physical_input(&mut self, ctx: &mut Context, input: InputType, started: bool) -> GameResult {
+        if self.replay.is_some() {
+            return Ok(());
+        }
+        if let InputType::MouseButtonEvent(button) = input {
+            self.apply(ctx, RecordedInput::MouseButton(button, started))?;
+        }
+
+        if self.offer_raw(ctx, input, started)? {
+            return Ok(());
+        }
+
+        let mods = ggez::input::keyboard::active_mods(ctx);
+        let effect = if started {
+            let effect = self.world.binding.resolve(input, mods);
+            if let Some(ev) = effect {
+                self.held.insert(input, ev);
+            }
+            effect
+        } else {
+            self.held.remove(&input).or_else(|| self.world.binding.resolve(input, mods))
+        };
+
+        if let Some(ev) = effect {
+            self.apply(ctx, RecordedInput::Effect(ev, started))?;
+        }
+        Ok(())
+    }
+
+    /// Like `physical_input`, for sticks and triggers that report how far
+    /// they're pushed. These ignore modifiers, since a stick is never
+    /// really let go of.
+    fn analog_input(&mut self, ctx: &mut Context, input: InputType, value: f32) -> GameResult {
+        if self.replay.is_some() {
+            return Ok(());
+        }
+        let pushed = value.abs() >= ANALOG_PRESS_THRESHOLD;
+        if self.offer_raw(ctx, input, pushed)? {
+            return Ok(());
+        }
+
+        if let Some(effect) = self.world.binding.resolve(input, KeyMods::NONE) {
+            self.apply(ctx, RecordedInput::Analog(effect, value))?;
+        }
+        Ok(())
+    }
+
+    /// Like `physical_input`, for a turn of the mouse wheel. Scenes hear
+    /// about it as a press that's released straight away.
+    fn wheel_input(
+        &mut self,
+        ctx: &mut Context,
+        direction: WheelDirection,
+        notches: f32,
+    ) -> GameResult {
+        let input = InputType::MouseWheelEvent(direction);
+        if self.offer_raw(ctx, input, true)? {
+            return Ok(());
+        }
+
+        let mods = ggez::input::keyboard::active_mods(ctx);
+        if let Some(ev) = self.world.binding.resolve(input, mods) {
+            self.apply(ctx, RecordedInput::Wheel(ev, notches))?;
+        }
+        Ok(())
+    }
+
+    /// Seconds since the last frame, either real or forced by `frame_time`.
+    fn frame_time(&self, ctx: &Context) -> f32 {
+        self.world.frame_time.unwrap_or_else(|| ggez::timer::delta(ctx).as_secs_f32())
+    }
+
+    /// The length of the coming frame, and the inputs that arrived before
+    /// it when replaying.
+    fn next_frame(&mut self, ctx: &mut Context) -> GameResult<f32> {
+        let frame = match &mut self.replay {
+            Some(replay) => replay.next_frame(),
+            None => return Ok(self.frame_time(ctx)),
+        };
+
+        match frame {
+            Some((inputs, dt)) => {
+                for input in inputs {
+                    self.apply(ctx, input)?;
+                }
+                Ok(dt)
+            }
+            None => {
+                eprintln!("Input replay finished, back to live input");
+                self.replay = None;
+                Ok(self.frame_time(ctx))
+            }
+        }
+    }
 }
 
 impl event::EventHandler for Game {
+    /// Runs as many fixed updates as the frame time covers.
     fn update(&mut self, ctx: &mut Context) -> GameResult {
-        self.world.input.update(ggez::timer::delta(ctx).as_secs_f32());
-        if let Some(scene) = self.scenes.last_mut() {
-            scene.as_mut().update(&mut self.world, ctx)?;
-        } else {
-            event::quit(ctx);
+        let frame_time = self.next_frame(ctx)?;
+        self.run_commands(ctx)?;
+        let timestep = self.world.timestep();
+        self.accumulator = (self.accumulator + frame_time).min(timestep * MAX_UPDATES_PER_FRAME);
+
+        // a little slack so frame times that are a multiple of the timestep
+        // don't lose an update to rounding
+        while self.accumulator >= timestep * 0.999 {
+            self.accumulator = (self.accumulator - timestep).max(0.0);
+            if let Some(scene) = self.scenes.last_mut() {
+                let transition = scene.as_mut().update(&mut self.world, ctx)?;
+                self.transition(ctx, transition)?;
+            } else {
+                event::quit(ctx);
+            }
+            // after the scene, so it sees the presses and scrolling of this frame
+            self.world.input.update(timestep);
+        }
+        self.world.interpolation = self.accumulator / timestep;
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.end_frame(frame_time)?;
         }
         Ok(())
     }
 
+    /// Draws the top scene along with whatever shows through it, bottom
+    /// first, and presents the frame.
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        if let Some(scene) = self.scenes.last_mut() {
-            scene.as_mut().draw(&mut self.world, ctx)?;
-        } else {
+        if self.scenes.is_empty() {
             event::quit(ctx);
+            return Ok(());
         }
+
+        let bottom = self.scenes.iter().rposition(|scene| !scene.is_overlay()).unwrap_or(0);
+        for scene in &mut self.scenes[bottom..] {
+            scene.as_mut().draw(&mut self.world, ctx)?;
+        }
+        graphics::present(ctx)?;
+        ggez::timer::yield_now();
         Ok(())
     }
 
-    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, _: f32, _: f32) {
-        let dx = if self.prev_x.is_nan() { 0.0 } else { x - self.prev_x };
-        let dy = if self.prev_y.is_nan() { 0.0 } else { y - self.prev_y };
-        self.prev_x = x;
-        self.prev_y = y;
-        self.input(ctx, self.input_binding.resolve_mousemotion(x, y, dx, dy), true).unwrap();
+    /// Passes on ggez's relative motion, which keeps coming when the cursor
+    /// is stuck against the edge of the window.
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, dx: f32, dy: f32) {
+        if self.replay.is_none() {
+            let ev = self.world.binding.resolve_mousemotion(x, y, dx, dy);
+            self.apply(ctx, RecordedInput::Effect(ev, true)).unwrap();
+        }
+    }
+
+    fn text_input_event(&mut self, ctx: &mut Context, character: char) {
+        if self.replay.is_none() {
+            self.apply(ctx, RecordedInput::Text(character)).unwrap();
+        }
+    }
+
+    fn mouse_wheel_event(&mut self, ctx: &mut Context, x: f32, y: f32) {
+        if self.replay.is_some() {
+            return;
+        }
+        self.apply(ctx, RecordedInput::Scroll(x, y)).unwrap();
+        let directions = [
+            (WheelDirection::Up, y),
+            (WheelDirection::Down, -y),
+            (WheelDirection::Right, x),
+            (WheelDirection::Left, -x),
+        ];
+        for (direction, notches) in directions.iter() {
+            if *notches > 0.0 {
+                self.wheel_input(ctx, *direction, *notches).unwrap();
+            }
+        }
     }
 
     fn key_down_event(
@@ -80,10 +457,7 @@ impl event::EventHandler for Game {
         _keymod: event::KeyMods,
         _repeat: bool,
     ) {
-        if let Some(ev) = self.input_binding.resolve_keycode(keycode) {
-            self.world.input.update_effect(ev, true);
-            self.input(ctx, ev, true).unwrap();
-        }
+        self.physical_input(ctx, InputType::KeyEvent(keycode), true).unwrap();
     }
 
     fn key_up_event(
@@ -92,10 +466,7 @@ impl event::EventHandler for Game {
         keycode: event::KeyCode,
         _keymod: event::KeyMods,
     ) {
-        if let Some(ev) = self.input_binding.resolve_keycode(keycode) {
-            self.world.input.update_effect(ev, false);
-            self.input(ctx, ev, false).unwrap();
-        }
+        self.physical_input(ctx, InputType::KeyEvent(keycode), false).unwrap();
     }
 
     fn mouse_button_down_event(
@@ -105,10 +476,7 @@ impl event::EventHandler for Game {
         _x: f32,
         _y: f32,
     ) {
-        if let Some(ev) = self.input_binding.resolve_mousebutton(button) {
-            self.world.input.update_effect(ev, true);
-            self.input(ctx, ev, true).unwrap();
-        }
+        self.physical_input(ctx, InputType::MouseButtonEvent(button), true).unwrap();
     }
 
     fn mouse_button_up_event(
@@ -118,10 +486,43 @@ impl event::EventHandler for Game {
         _x: f32,
         _y: f32,
     ) {
-        if let Some(ev) = self.input_binding.resolve_mousebutton(button) {
-            self.world.input.update_effect(ev, false);
-            self.input(ctx, ev, false).unwrap();
+        self.physical_input(ctx, InputType::MouseButtonEvent(button), false).unwrap();
+    }
+
+    fn gamepad_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        button: event::Button,
+        _id: event::GamepadId,
+    ) {
+        self.physical_input(ctx, InputType::GamepadButtonEvent(button), true).unwrap();
+    }
+
+    fn gamepad_button_up_event(
+        &mut self,
+        ctx: &mut Context,
+        button: event::Button,
+        _id: event::GamepadId,
+    ) {
+        self.physical_input(ctx, InputType::GamepadButtonEvent(button), false).unwrap();
+    }
+
+    fn gamepad_axis_event(
+        &mut self,
+        ctx: &mut Context,
+        axis: event::Axis,
+        value: f32,
+        _id: event::GamepadId,
+    ) {
+        self.analog_input(ctx, InputType::GamepadAxisEvent(axis), value).unwrap();
+    }
+
+    /// Lets every scene save its state when the window is closed.
+    fn quit_event(&mut self, ctx: &mut Context) -> bool {
+        if let Err(err) = self.exit_all(ctx) {
+            eprintln!("Error while quitting: {}", err);
         }
+        false
     }
 
     fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
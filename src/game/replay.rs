@@ -0,0 +1,335 @@
+//! Recording the input of a session so it can be played back exactly.
+//!
+//! The log has a `set key value` line per setting the game started with,
+//! then one line per input, prefixed with the index of the frame it
+//! arrived in. Each frame ends with a `dt` line holding the seconds it
+//! covered, so a replay runs the same updates with the same inputs:
+//!
+//! ```text
+//! 0 press Throttle+
+//! 0 dt 0.016667
+//! 1 raw press Escape
+//! 1 text 97
+//! 1 button press MouseLeft
+//! 1 scroll 0 1
+//! 1 dt 0.016801
+//! ```
+//!
+//! Inputs are stored after they've gone through the bindings, so a replay
+//! doesn't depend on the controls file. Mouse buttons and scrolling are
+//! also stored as they are, bound or not, since the input state keeps
+//! track of them for scenes to read, along with where the pointer is.
+
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+
+use ggez::event::MouseButton;
+use ggez::GameError;
+use ggez::GameResult;
+
+use super::input::controls;
+use super::input::types::InputEffect;
+use super::input::types::InputType;
+use super::Event;
+
+const REPLAY_HEADER: &str = "# voxelspace input v1";
+
+/// One thing that happened to the input during a frame.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RecordedInput {
+    /// A bound action was pressed or released, or the pointer moved
+    Effect(Event, bool),
+    /// A stick or trigger bound to an action moved to a raw value
+    Analog(Event, f32),
+    /// The mouse wheel turned by some notches towards an action
+    Wheel(Event, f32),
+    /// A physical input the scene took before it reached the bindings
+    Raw(InputType, bool),
+    /// A character was typed
+    Text(char),
+    /// A mouse button was pressed or released, whatever it's bound to
+    MouseButton(MouseButton, bool),
+    /// The mouse wheel turned, horizontally and vertically
+    Scroll(f32, f32),
+}
+
+impl RecordedInput {
+    fn to_line(self) -> String {
+        let pressed = |started| if started { "press" } else { "release" };
+        match self {
+            RecordedInput::Effect(InputEffect::Pointer(x, y, dx, dy), _) => {
+                format!("pointer {} {} {} {}", x, y, dx, dy)
+            }
+            RecordedInput::Effect(effect, started) => {
+                format!("{} {}", pressed(started), controls::action_name(&effect))
+            }
+            RecordedInput::Analog(effect, value) => {
+                format!("analog {} {}", controls::action_name(&effect), value)
+            }
+            RecordedInput::Wheel(effect, notches) => {
+                format!("wheel {} {}", controls::action_name(&effect), notches)
+            }
+            RecordedInput::Raw(input, started) => {
+                format!("raw {} {}", pressed(started), controls::input_name(input))
+            }
+            // by code, since the character could be a space
+            RecordedInput::Text(character) => format!("text {}", character as u32),
+            RecordedInput::MouseButton(button, started) => {
+                let input = InputType::MouseButtonEvent(button);
+                format!("button {} {}", pressed(started), controls::input_name(input))
+            }
+            RecordedInput::Scroll(x, y) => format!("scroll {} {}", x, y),
+        }
+    }
+
+    fn from_words(words: &[&str]) -> Result<Self, String> {
+        let action = |name: &str| {
+            controls::parse_action(name).ok_or_else(|| format!("unknown action {:?}", name))
+        };
+        let number =
+            |value: &str| value.parse::<f32>().map_err(|_| format!("invalid number {:?}", value));
+        let started = |word: &str| match word {
+            "press" => Ok(true),
+            "release" => Ok(false),
+            _ => Err(format!("expected press or release, got {:?}", word)),
+        };
+
+        match words {
+            ["pointer", x, y, dx, dy] => Ok(RecordedInput::Effect(
+                InputEffect::Pointer(number(x)?, number(y)?, number(dx)?, number(dy)?),
+                true,
+            )),
+            ["analog", name, value] => Ok(RecordedInput::Analog(action(name)?, number(value)?)),
+            ["wheel", name, notches] => Ok(RecordedInput::Wheel(action(name)?, number(notches)?)),
+            ["raw", pressed, name] => {
+                let input = controls::parse_input(name)
+                    .ok_or_else(|| format!("unknown input {:?}", name))?;
+                Ok(RecordedInput::Raw(input, started(pressed)?))
+            }
+            ["text", code] => code
+                .parse()
+                .ok()
+                .and_then(std::char::from_u32)
+                .map(RecordedInput::Text)
+                .ok_or_else(|| format!("invalid character code {:?}", code)),
+            ["button", pressed, name] => match controls::parse_input(name) {
+                Some(InputType::MouseButtonEvent(button)) => {
+                    Ok(RecordedInput::MouseButton(button, started(pressed)?))
+                }
+                _ => Err(format!("unknown mouse button {:?}", name)),
+            },
+            ["scroll", x, y] => Ok(RecordedInput::Scroll(number(x)?, number(y)?)),
+            [pressed, name] => Ok(RecordedInput::Effect(action(name)?, started(pressed)?)),
+            _ => Err("unknown input event".to_owned()),
+        }
+    }
+}
+
+/// Writes the input log as the game runs.
+#[derive(Debug)]
+pub struct InputRecorder {
+    writer: BufWriter<File>,
+    frame: u64,
+}
+
+impl InputRecorder {
+    /// Starts a log at `path`, noting the settings the game starts with.
+    pub fn create(path: &Path, settings: &[(String, String)]) -> GameResult<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{}", REPLAY_HEADER)?;
+        for (key, value) in settings {
+            writeln!(writer, "set {} {}", key, value)?;
+        }
+        Ok(InputRecorder { writer, frame: 0 })
+    }
+
+    pub fn record(&mut self, input: RecordedInput) -> GameResult {
+        writeln!(self.writer, "{} {}", self.frame, input.to_line())?;
+        Ok(())
+    }
+
+    /// Closes the current frame. The log is flushed every frame so it
+    /// survives the game crashing, which is when it's most useful.
+    pub fn end_frame(&mut self, dt: f32) -> GameResult {
+        writeln!(self.writer, "{} dt {}", self.frame, dt)?;
+        self.writer.flush()?;
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+/// The inputs of one recorded frame.
+#[derive(Debug, Clone, Default)]
+struct ReplayFrame {
+    inputs: Vec<RecordedInput>,
+    dt: f32,
+}
+
+/// An input log being played back.
+#[derive(Debug, Clone, Default)]
+pub struct InputReplay {
+    settings: Vec<(String, String)>,
+    frames: Vec<ReplayFrame>,
+    next: usize,
+}
+
+impl InputReplay {
+    pub fn open(path: &Path) -> GameResult<Self> {
+        InputReplay::load(BufReader::new(File::open(path)?))
+            .map_err(|err| GameError::ResourceLoadError(format!("{}: {}", path.display(), err)))
+    }
+
+    /// Reads a log. Inputs after the last complete frame are dropped, since
+    /// there's no telling how long that frame was.
+    pub fn load<R: BufRead>(reader: R) -> GameResult<Self> {
+        let mut replay = InputReplay::default();
+        let mut frame = ReplayFrame::default();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse_error = |reason: &str| {
+                GameError::ResourceLoadError(format!(
+                    "input log line {}: {} in {:?}",
+                    index + 1,
+                    reason,
+                    line
+                ))
+            };
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if let ["set", key, value] = words[..] {
+                if !replay.frames.is_empty() || !frame.inputs.is_empty() {
+                    return Err(parse_error("settings have to come before the inputs"));
+                }
+                replay.settings.push((key.to_owned(), value.to_owned()));
+                continue;
+            }
+
+            match words[0].parse::<usize>() {
+                Ok(number) if number == replay.frames.len() => (),
+                Ok(_) => return Err(parse_error("frames are out of order")),
+                Err(_) => return Err(parse_error("expected a frame number")),
+            }
+            match words[1..] {
+                ["dt", dt] => {
+                    frame.dt = dt.parse().map_err(|_| parse_error("invalid dt"))?;
+                    replay.frames.push(std::mem::take(&mut frame));
+                }
+                _ => {
+                    let input =
+                        RecordedInput::from_words(&words[1..]).map_err(|err| parse_error(&err))?;
+                    frame.inputs.push(input);
+                }
+            }
+        }
+        Ok(replay)
+    }
+
+    /// Settings the recorded game started with, as `key`, `value` pairs.
+    pub fn settings(&self) -> &[(String, String)] {
+        &self.settings
+    }
+
+    /// Inputs and length of the next frame, or `None` once the log is done.
+    pub fn next_frame(&mut self) -> Option<(Vec<RecordedInput>, f32)> {
+        let frame = self.frames.get(self.next)?;
+        self.next += 1;
+        Some((frame.inputs.clone(), frame.dt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ggez::event::KeyCode;
+
+    use super::*;
+    use crate::game::input::types::WheelDirection;
+    use crate::game::input::Axis;
+    use crate::game::input::Button;
+
+    fn round_trip(input: RecordedInput) -> RecordedInput {
+        let line = input.to_line();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        RecordedInput::from_words(&words).unwrap()
+    }
+
+    #[test]
+    fn inputs_survive_a_round_trip() {
+        let inputs = [
+            RecordedInput::Effect(InputEffect::Axis(Axis::Throttle, true), true),
+            RecordedInput::Effect(InputEffect::Button(Button::Pause), false),
+            RecordedInput::Effect(InputEffect::Pointer(10.0, 20.5, -1.25, 3.0), true),
+            RecordedInput::Analog(InputEffect::Axis(Axis::Turn, true), -0.75),
+            RecordedInput::Wheel(InputEffect::Axis(Axis::Fov, false), 2.0),
+            RecordedInput::Raw(InputType::KeyEvent(KeyCode::Escape), true),
+            RecordedInput::Raw(InputType::MouseWheelEvent(WheelDirection::Up), false),
+            RecordedInput::Text('a'),
+            RecordedInput::Text(' '),
+            RecordedInput::Text('é'),
+            RecordedInput::MouseButton(MouseButton::Left, true),
+            RecordedInput::MouseButton(MouseButton::Other(5), false),
+            RecordedInput::Scroll(0.5, -1.0),
+        ];
+        for input in &inputs {
+            assert_eq!(round_trip(*input), *input);
+        }
+    }
+
+    #[test]
+    fn logs_load_by_frame() {
+        let log = "# voxelspace input v1
+set map 4
+set fov 70
+0 press Throttle+
+0 dt 0.016
+1 button press MouseLeft
+1 scroll 0 1
+1 dt 0.02
+2 release Throttle+
+";
+        let mut replay = InputReplay::load(log.as_bytes()).unwrap();
+        assert_eq!(
+            replay.settings(),
+            &[("map".to_owned(), "4".to_owned()), ("fov".to_owned(), "70".to_owned())]
+        );
+
+        let throttle = InputEffect::Axis(Axis::Throttle, true);
+        let (inputs, dt) = replay.next_frame().unwrap();
+        assert_eq!(inputs, vec![RecordedInput::Effect(throttle, true)]);
+        assert_eq!(dt, 0.016);
+        let (inputs, dt) = replay.next_frame().unwrap();
+        assert_eq!(
+            inputs,
+            vec![
+                RecordedInput::MouseButton(MouseButton::Left, true),
+                RecordedInput::Scroll(0.0, 1.0)
+            ]
+        );
+        assert_eq!(dt, 0.02);
+        // the last frame never finished
+        assert!(replay.next_frame().is_none());
+    }
+
+    #[test]
+    fn bad_logs_are_errors() {
+        let load = |log: &str| InputReplay::load(log.as_bytes());
+        assert!(load("0 press Throttle+\n0 dt 0.1\nset map 4").is_err());
+        assert!(load("1 press Throttle+").is_err());
+        assert!(load("0 dt 0.1\n0 dt 0.1").is_err());
+        assert!(load("first press Throttle+").is_err());
+        assert!(load("0 dt fast").is_err());
+        assert!(load("0 press Launch").is_err());
+        assert!(load("0 hold Throttle+").is_err());
+        assert!(load("0 button press Escape").is_err());
+        assert!(load("0 text -1").is_err());
+        assert!(load("0 scroll 1").is_err());
+    }
+}
@@ -1,11 +1,36 @@
+use std::any::Any;
+
 use ggez::Context;
 use ggez::GameResult;
 
+use super::input::types::InputType;
 use super::Event;
 use super::World;
 
+/// What a scene wants done to the scene stack.
+pub enum Transition {
+    /// Stay on the current scene
+    None,
+    /// Put a scene on top of the current one, which waits underneath
+    Push(Box<dyn Scene>),
+    /// Remove the current scene, going back to the one below
+    Pop,
+    /// Remove the current scene, handing a result to the one below through
+    /// `on_resume`
+    Return(Box<dyn Any>),
+    /// Stay on the current scene, handing something to the one below
+    /// through `on_notify`
+    Notify(Box<dyn Any>),
+    /// Swap the current scene for another
+    Replace(Box<dyn Scene>),
+    /// Leave every scene and close the game
+    Quit,
+}
+
 pub trait Scene {
-    fn update(&mut self, state: &mut World, ctx: &mut Context) -> GameResult;
+    fn update(&mut self, state: &mut World, ctx: &mut Context) -> GameResult<Transition>;
+    /// Draws the scene, without presenting the frame; `Game` does that once
+    /// every visible scene has drawn.
     fn draw(&mut self, state: &mut World, ctx: &mut Context) -> GameResult;
     fn input(
         &mut self,
@@ -13,5 +38,88 @@ pub trait Scene {
         ctx: &mut Context,
         event: Event,
         started: bool,
-    ) -> GameResult;
+    ) -> GameResult<Transition>;
+
+    /// Gets every key and mouse button before it's looked up in the
+    /// bindings, for scenes that need to know exactly what was pressed.
+    /// Returns whether the input was used up, in which case `input` won't
+    /// hear about it.
+    fn raw_input(
+        &mut self,
+        _state: &mut World,
+        _ctx: &mut Context,
+        _input: InputType,
+        _started: bool,
+    ) -> GameResult<bool> {
+        Ok(false)
+    }
+
+    /// Gets each character typed, for scenes that take text.
+    fn text_input(
+        &mut self,
+        _state: &mut World,
+        _ctx: &mut Context,
+        _character: char,
+    ) -> GameResult {
+        Ok(())
+    }
+
+    /// Runs a console command registered in `World::commands`, returning
+    /// what to print. `None` means the command isn't this scene's, and it's
+    /// offered to the scene below instead.
+    fn command(
+        &mut self,
+        _state: &mut World,
+        _ctx: &mut Context,
+        _name: &str,
+        _args: &[&str],
+    ) -> GameResult<Option<String>> {
+        Ok(None)
+    }
+
+    /// Whether the scene is drawn over the one below it rather than
+    /// covering it. The scenes below an overlay keep drawing, down to the
+    /// first one that isn't an overlay, but only the top scene is updated.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    /// Called when the scene is put on the stack.
+    fn on_enter(&mut self, _state: &mut World, _ctx: &mut Context) -> GameResult {
+        Ok(())
+    }
+
+    /// Called when the scene is taken off the stack, including when the game
+    /// quits, so it can save anything it needs to.
+    fn on_exit(&mut self, _state: &mut World, _ctx: &mut Context) -> GameResult {
+        Ok(())
+    }
+
+    /// Called when another scene is pushed on top of this one, which stops
+    /// it being updated.
+    fn on_pause(&mut self, _state: &mut World, _ctx: &mut Context) -> GameResult {
+        Ok(())
+    }
+
+    /// Called when the scene on top of this one is popped, making it the top
+    /// scene again, with whatever the popped scene handed back.
+    fn on_resume(
+        &mut self,
+        _state: &mut World,
+        _ctx: &mut Context,
+        _result: Option<Box<dyn Any>>,
+    ) -> GameResult {
+        Ok(())
+    }
+
+    /// Called with whatever the scene on top of this one handed down while
+    /// staying open.
+    fn on_notify(
+        &mut self,
+        _state: &mut World,
+        _ctx: &mut Context,
+        _message: Box<dyn Any>,
+    ) -> GameResult {
+        Ok(())
+    }
 }
@@ -0,0 +1,323 @@
+use std::f32::consts::PI;
+use std::fs;
+use std::time::Duration;
+use std::time::Instant;
+
+use ggez::event;
+use ggez::graphics;
+use ggez::Context;
+use ggez::GameError;
+use ggez::GameResult;
+use glam::*;
+
+use super::camerapath::CameraPath;
+use super::camerapath::Easing;
+use super::camerapath::Interpolation;
+use super::camerapath::Keyframe;
+use super::camerapath::PathPlayback;
+use super::camerapath::PathStyle;
+use super::camerapath::Timing;
+use super::map::Map;
+use super::MapDrawParam;
+use super::SKY;
+use crate::cli::Args;
+use crate::game::Event;
+use crate::game::Scene;
+use crate::game::Transition;
+use crate::game::World;
+
+const DEFAULT_MAP: i32 = 1;
+const DEFAULT_FRAMES: usize = 1000;
+const DEFAULT_WARMUP: usize = 30;
+const DEFAULT_WIDTH: f32 = 800.0;
+const DEFAULT_HEIGHT: f32 = 600.0;
+const SIMULATED_FPS: f32 = 60.0;
+const PATH_RADIUS: f32 = 300.0;
+const PATH_CENTER: f32 = 512.0;
+const PATH_HEIGHT: f32 = 260.0;
+const PATH_KEYFRAMES: usize = 16;
+
+pub const BENCH_USAGE: &str = "usage: voxelspace bench [--map N] [--frames N] [--warmup N] \
+[--size WIDTHxHEIGHT] [--path FILE] [--json FILE]";
+
+/// Settings for a benchmark run, usually parsed from the command line.
+#[derive(Debug, Clone)]
+pub struct BenchConfig {
+    pub map_id: i32,
+    /// Number of frames to measure
+    pub frames: usize,
+    /// Number of frames rendered before measuring starts
+    pub warmup: usize,
+    pub width: f32,
+    pub height: f32,
+    /// Camera path to fly, or a fixed circle around the map if `None`
+    pub path: Option<String>,
+    /// Where to write the JSON report, `-` for stdout
+    pub json: Option<String>,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        BenchConfig {
+            map_id: DEFAULT_MAP,
+            frames: DEFAULT_FRAMES,
+            warmup: DEFAULT_WARMUP,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            path: None,
+            json: None,
+        }
+    }
+}
+
+impl BenchConfig {
+    /// Parses the arguments following `bench`.
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> GameResult<Self> {
+        let mut args = Args::new(args, BENCH_USAGE);
+        let mut config = BenchConfig::default();
+        while let Some(flag) = args.next_flag() {
+            match flag.as_str() {
+                "--map" => config.map_id = args.value(&flag)?,
+                "--frames" => config.frames = args.value(&flag)?,
+                "--warmup" => config.warmup = args.value(&flag)?,
+                "--size" => {
+                    let (width, height) = args.size(&flag)?;
+                    config.width = width;
+                    config.height = height;
+                }
+                "--path" => config.path = Some(args.string(&flag)?),
+                "--json" => config.json = Some(args.string(&flag)?),
+                _ => return Err(args.error(&format!("unknown argument {}", flag))),
+            }
+        }
+
+        if config.frames == 0 {
+            return Err(args.error("--frames must be at least 1"));
+        }
+        Ok(config)
+    }
+}
+
+/// Summary of a list of timings, in milliseconds.
+#[derive(Debug, Copy, Clone)]
+pub struct Stats {
+    pub min: f64,
+    pub mean: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    fn new(samples: &[Duration]) -> Self {
+        let mut ms: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+        ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        // nearest rank percentiles
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * ms.len() as f64).ceil() as usize;
+            ms[rank.clamp(1, ms.len()) - 1]
+        };
+
+        Stats {
+            min: ms[0],
+            mean: ms.iter().sum::<f64>() / ms.len() as f64,
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+            max: ms[ms.len() - 1],
+        }
+    }
+
+    fn to_json(self) -> String {
+        format!(
+            r#"{{"min": {:.4}, "mean": {:.4}, "p95": {:.4}, "p99": {:.4}, "max": {:.4}}}"#,
+            self.min, self.mean, self.p95, self.p99, self.max
+        )
+    }
+}
+
+/// Flies a fixed camera path for a set number of frames and reports how
+/// long they took.
+///
+/// The camera advances by a fixed step every frame no matter how long the
+/// frame took, so every run renders exactly the same images.
+#[derive(Debug)]
+pub struct BenchState {
+    config: BenchConfig,
+    map: Map,
+    draw_param: MapDrawParam,
+    playback: PathPlayback,
+    frame: usize,
+    last_frame: Option<Instant>,
+    frame_times: Vec<Duration>,
+    map_draw_times: Vec<Duration>,
+}
+
+impl BenchState {
+    pub fn new(ctx: &mut Context, config: BenchConfig) -> GameResult<Self> {
+        let path = match &config.path {
+            Some(file) => CameraPath::load(std::io::BufReader::new(fs::File::open(file)?))?,
+            None => circle_path(),
+        };
+        let style = PathStyle {
+            interpolation: Interpolation::CatmullRom,
+            easing: Easing::Linear,
+            timing: Timing::Duration((config.warmup + config.frames) as f32 / SIMULATED_FPS),
+        };
+        let playback = PathPlayback::new(&path, style)
+            .ok_or_else(|| GameError::ConfigError("benchmark camera path is empty".to_owned()))?;
+
+        Ok(BenchState {
+            map: Map::new(ctx, config.map_id)?,
+            draw_param: MapDrawParam::default(),
+            playback,
+            frame: 0,
+            last_frame: None,
+            frame_times: Vec::with_capacity(config.frames),
+            map_draw_times: Vec::with_capacity(config.frames),
+            config,
+        })
+    }
+
+    fn report(&self) -> GameResult {
+        let frame = Stats::new(&self.frame_times);
+        let map_draw = Stats::new(&self.map_draw_times);
+        let (width, height) = (self.config.width, self.config.height);
+
+        println!(
+            "map {} at {}x{}, {} frames after {} warmup",
+            self.config.map_id, width, height, self.config.frames, self.config.warmup
+        );
+        for (name, stats) in &[("frame", frame), ("Map::draw", map_draw)] {
+            println!(
+                "{:>10}: min {:.2}ms  mean {:.2}ms  p95 {:.2}ms  p99 {:.2}ms  max {:.2}ms",
+                name, stats.min, stats.mean, stats.p95, stats.p99, stats.max
+            );
+        }
+
+        if let Some(json_file) = &self.config.json {
+            let json = format!(
+                r#"{{"map": {}, "width": {}, "height": {}, "frames": {}, "warmup": {}, "frame_ms": {}, "map_draw_ms": {}}}"#,
+                self.config.map_id,
+                width,
+                height,
+                self.config.frames,
+                self.config.warmup,
+                frame.to_json(),
+                map_draw.to_json()
+            );
+            if json_file == "-" {
+                println!("{}", json);
+            } else {
+                fs::write(json_file, json + "\n")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Scene for BenchState {
+    fn update(&mut self, state: &mut World, _ctx: &mut Context) -> GameResult<Transition> {
+        self.playback.update(state.timestep(), &mut self.draw_param);
+        Ok(Transition::None)
+    }
+
+    fn draw(&mut self, _state: &mut World, ctx: &mut Context) -> GameResult {
+        // a frame lasts from the start of one draw to the start of the next
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            if self.frame > self.config.warmup {
+                self.frame_times.push(now - last_frame);
+            }
+        }
+        self.last_frame = Some(now);
+
+        if self.frame >= self.config.warmup + self.config.frames {
+            self.report()?;
+            event::quit(ctx);
+            return Ok(());
+        }
+
+        graphics::clear(ctx, SKY);
+        let map_draw_start = Instant::now();
+        self.map.draw(ctx, &self.draw_param, None)?;
+        if self.frame >= self.config.warmup {
+            self.map_draw_times.push(map_draw_start.elapsed());
+        }
+
+        self.frame += 1;
+        Ok(())
+    }
+
+    fn input(
+        &mut self,
+        _state: &mut World,
+        _ctx: &mut Context,
+        _event: Event,
+        _started: bool,
+    ) -> GameResult<Transition> {
+        Ok(Transition::None)
+    }
+
+    /// Runs exactly one update per frame, so every run draws the same views.
+    fn on_enter(&mut self, state: &mut World, _ctx: &mut Context) -> GameResult {
+        state.tick_rate = SIMULATED_FPS;
+        state.frame_time = Some(1.0 / SIMULATED_FPS);
+        Ok(())
+    }
+}
+
+/// A loop around the middle of the map, looking along the direction of travel.
+fn circle_path() -> CameraPath {
+    let keyframes = (0..=PATH_KEYFRAMES)
+        .map(|i| {
+            let angle = i as f32 / PATH_KEYFRAMES as f32 * 2.0 * PI;
+            let camera = Vec3::new(
+                PATH_CENTER + PATH_RADIUS * angle.cos(),
+                PATH_HEIGHT,
+                PATH_CENTER + PATH_RADIUS * angle.sin(),
+            );
+            let param = MapDrawParam { camera, rotation: angle + PI / 2.0, ..Default::default() };
+            Keyframe::new(i as f32, &param)
+        })
+        .collect();
+    CameraPath { keyframes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(samples: &[u64]) -> Vec<Duration> {
+        samples.iter().map(|&ms| Duration::from_millis(ms)).collect()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        // shuffled, since the samples come in frame order
+        let samples: Vec<u64> = (1..=100).map(|i| i * 37 % 101).collect();
+        let stats = Stats::new(&millis(&samples));
+        assert_close(stats.min, 1.0);
+        assert_close(stats.mean, 50.5);
+        assert_close(stats.p95, 95.0);
+        assert_close(stats.p99, 99.0);
+        assert_close(stats.max, 100.0);
+    }
+
+    #[test]
+    fn small_samples_round_the_rank_up() {
+        let stats = Stats::new(&millis(&[4, 1, 3, 2, 10, 5, 6, 9, 8, 7]));
+        // rank 9.5 of 10 rounds up to the slowest frame
+        assert_close(stats.p95, 10.0);
+        assert_close(stats.p99, 10.0);
+        assert_close(stats.mean, 5.5);
+
+        let stats = Stats::new(&millis(&[16]));
+        assert_close(stats.min, 16.0);
+        assert_close(stats.p99, 16.0);
+    }
+}
@@ -0,0 +1,249 @@
+use std::io::BufRead;
+use std::io::Write;
+
+use ggez::GameError;
+use ggez::GameResult;
+use glam::*;
+
+use super::MapDrawParam;
+use super::MAP_COUNT;
+
+const BOOKMARKS_HEADER: &str = "# voxelspace bookmarks v1";
+
+/// A named camera view on a map.
+#[derive(Derivative, Clone)]
+#[derivative(Debug)]
+pub struct Bookmark {
+    pub name: String,
+    pub map_id: i32,
+    #[derivative(Debug(format_with = "super::vec3_fmt"))]
+    pub camera: Vec3,
+    pub rotation: f32,
+    pub horizon: f32,
+    pub fov: f32,
+    pub height_scale: f32,
+}
+
+impl Bookmark {
+    pub fn new(name: &str, map_id: i32, param: &MapDrawParam) -> Self {
+        Bookmark {
+            name: name.to_owned(),
+            map_id,
+            camera: param.camera,
+            rotation: param.rotation,
+            horizon: param.horizon,
+            fov: param.fov,
+            height_scale: param.height_scale,
+        }
+    }
+
+    /// Writes the view into the draw parameters.
+    pub fn apply(&self, param: &mut MapDrawParam) {
+        param.camera = self.camera;
+        param.rotation = self.rotation;
+        param.horizon = self.horizon;
+        param.fov = self.fov;
+        param.height_scale = self.height_scale;
+    }
+}
+
+/// Every saved bookmark, stored on disk as one whitespace separated
+/// `map name x y z rotation horizon fov height_scale` line per bookmark.
+/// Names can't contain whitespace and are unique on each map.
+#[derive(Debug, Clone, Default)]
+pub struct Bookmarks {
+    bookmarks: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    pub fn load<R: BufRead>(reader: R) -> GameResult<Self> {
+        let mut bookmarks = Bookmarks::default();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse_error = |reason: &str| {
+                GameError::ResourceLoadError(format!(
+                    "bookmarks line {}: {} in {:?}",
+                    index + 1,
+                    reason,
+                    line
+                ))
+            };
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.len() != 9 {
+                return Err(parse_error(&format!("expected 9 values, got {}", words.len())));
+            }
+            let map_id = match words[0].parse() {
+                Ok(map_id) if (1..=MAP_COUNT).contains(&map_id) => map_id,
+                _ => return Err(parse_error("invalid map")),
+            };
+            let values = words[2..]
+                .iter()
+                .map(|value| value.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| parse_error(&err.to_string()))?;
+            bookmarks.bookmarks.push(Bookmark {
+                name: words[1].to_owned(),
+                map_id,
+                camera: Vec3::new(values[0], values[1], values[2]),
+                rotation: values[3],
+                horizon: values[4],
+                fov: values[5],
+                height_scale: values[6],
+            });
+        }
+        Ok(bookmarks)
+    }
+
+    pub fn save<W: Write>(&self, mut writer: W) -> GameResult {
+        writeln!(writer, "{}", BOOKMARKS_HEADER)?;
+        writeln!(writer, "# map name x y z rotation horizon fov height_scale")?;
+        for bookmark in &self.bookmarks {
+            let camera = bookmark.camera;
+            writeln!(
+                writer,
+                "{} {} {} {} {} {} {} {} {}",
+                bookmark.map_id,
+                bookmark.name,
+                camera.x(),
+                camera.y(),
+                camera.z(),
+                bookmark.rotation,
+                bookmark.horizon,
+                bookmark.fov,
+                bookmark.height_scale
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The bookmarks on a map, in the order they were saved.
+    pub fn on_map(&self, map_id: i32) -> Vec<&Bookmark> {
+        self.bookmarks.iter().filter(|bookmark| bookmark.map_id == map_id).collect()
+    }
+
+    /// Finds a bookmark by name, preferring the given map.
+    pub fn find(&self, map_id: i32, name: &str) -> Option<&Bookmark> {
+        let named = |bookmark: &&Bookmark| bookmark.name == name;
+        let on_map = self.bookmarks.iter().filter(|bookmark| bookmark.map_id == map_id).find(named);
+        on_map.or_else(|| self.bookmarks.iter().find(named))
+    }
+
+    /// Adds a bookmark, replacing any with the same name on the same map.
+    pub fn insert(&mut self, bookmark: Bookmark) {
+        let existing = self
+            .bookmarks
+            .iter_mut()
+            .find(|old| old.map_id == bookmark.map_id && old.name == bookmark.name);
+        match existing {
+            Some(old) => *old = bookmark,
+            None => self.bookmarks.push(bookmark),
+        }
+    }
+
+    /// Removes the named bookmark from a map, returning whether there was one.
+    pub fn remove(&mut self, map_id: i32, name: &str) -> bool {
+        let count = self.bookmarks.len();
+        self.bookmarks.retain(|bookmark| bookmark.map_id != map_id || bookmark.name != name);
+        self.bookmarks.len() != count
+    }
+
+    /// The lowest number not yet used as a name on a map, for bookmarks
+    /// saved with a hotkey.
+    pub fn unused_name(&self, map_id: i32) -> String {
+        let bookmarks = self.on_map(map_id);
+        let number = (1..)
+            .find(|number: &usize| {
+                let name = number.to_string();
+                !bookmarks.iter().any(|bookmark| bookmark.name == name)
+            })
+            .unwrap_or_default();
+        number.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(name: &str, map_id: i32, x: f32) -> Bookmark {
+        Bookmark {
+            name: name.to_owned(),
+            map_id,
+            camera: Vec3::new(x, 120.5, -3.0),
+            rotation: 1.25,
+            horizon: 40.0,
+            fov: 1.5,
+            height_scale: 2.0,
+        }
+    }
+
+    #[test]
+    fn bookmarks_survive_a_round_trip() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.insert(bookmark("1", 1, 10.0));
+        bookmarks.insert(bookmark("canyon", MAP_COUNT, 512.75));
+
+        let mut file = Vec::new();
+        bookmarks.save(&mut file).unwrap();
+        let loaded = Bookmarks::load(&file[..]).unwrap();
+
+        assert_eq!(loaded.bookmarks.len(), 2);
+        for (loaded, saved) in loaded.bookmarks.iter().zip(&bookmarks.bookmarks) {
+            assert_eq!(loaded.name, saved.name);
+            assert_eq!(loaded.map_id, saved.map_id);
+            assert_eq!(loaded.camera, saved.camera);
+            assert_eq!(loaded.rotation, saved.rotation);
+            assert_eq!(loaded.horizon, saved.horizon);
+            assert_eq!(loaded.fov, saved.fov);
+            assert_eq!(loaded.height_scale, saved.height_scale);
+        }
+    }
+
+    #[test]
+    fn bad_lines_are_errors() {
+        let load = |file: &str| Bookmarks::load(file.as_bytes());
+        assert!(load("1 home 0 0 0 0 0 1.5 1").is_ok());
+        assert!(load("0 home 0 0 0 0 0 1.5 1").is_err());
+        assert!(load(&format!("{} home 0 0 0 0 0 1.5 1", MAP_COUNT + 1)).is_err());
+        assert!(load("first home 0 0 0 0 0 1.5 1").is_err());
+        assert!(load("1 home 0 0 0 0 0 1.5").is_err());
+        assert!(load("1 home 0 0 0 0 0 1.5 1 1").is_err());
+        assert!(load("1 home 0 0 up 0 0 1.5 1").is_err());
+    }
+
+    #[test]
+    fn names_are_unique_per_map() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.insert(bookmark("home", 1, 10.0));
+        bookmarks.insert(bookmark("home", 2, 20.0));
+        bookmarks.insert(bookmark("home", 1, 30.0));
+        assert_eq!(bookmarks.on_map(1).len(), 1);
+
+        // the current map's bookmark wins, then any other
+        assert_eq!(bookmarks.find(1, "home").unwrap().camera.x(), 30.0);
+        assert_eq!(bookmarks.find(2, "home").unwrap().camera.x(), 20.0);
+        assert_eq!(bookmarks.find(3, "home").unwrap().map_id, 1);
+        assert!(bookmarks.find(1, "away").is_none());
+
+        assert!(bookmarks.remove(1, "home"));
+        assert!(!bookmarks.remove(1, "home"));
+        assert_eq!(bookmarks.find(1, "home").unwrap().map_id, 2);
+    }
+
+    #[test]
+    fn unused_names_fill_gaps() {
+        let mut bookmarks = Bookmarks::default();
+        assert_eq!(bookmarks.unused_name(1), "1");
+        bookmarks.insert(bookmark("1", 1, 0.0));
+        bookmarks.insert(bookmark("3", 1, 0.0));
+        bookmarks.insert(bookmark("2", 2, 0.0));
+        assert_eq!(bookmarks.unused_name(1), "2");
+        assert_eq!(bookmarks.unused_name(2), "1");
+    }
+}
@@ -0,0 +1,443 @@
+use std::f32::consts::PI;
+use std::io::BufRead;
+use std::io::Write;
+
+use ggez::GameError;
+use ggez::GameResult;
+use glam::*;
+
+use super::MapDrawParam;
+
+const PATH_HEADER: &str = "# voxelspace camera path v1";
+const RECORD_INTERVAL: f32 = 0.5;
+
+/// A single recorded camera pose.
+#[derive(Derivative, Copy, Clone)]
+#[derivative(Debug)]
+pub struct Keyframe {
+    /// Seconds since the recording started
+    pub time: f32,
+    #[derivative(Debug(format_with = "super::vec3_fmt"))]
+    pub camera: Vec3,
+    pub rotation: f32,
+    pub horizon: f32,
+    pub fov: f32,
+}
+
+impl Keyframe {
+    pub fn new(time: f32, param: &MapDrawParam) -> Self {
+        Keyframe {
+            time,
+            camera: param.camera,
+            rotation: param.rotation,
+            horizon: param.horizon,
+            fov: param.fov,
+        }
+    }
+
+    /// Writes this pose into the draw parameters.
+    pub fn apply(&self, param: &mut MapDrawParam) {
+        let full = 2.0 * PI;
+        param.camera = self.camera;
+        param.rotation = (self.rotation % full + full) % full;
+        param.horizon = self.horizon;
+        param.fov = self.fov;
+    }
+
+    fn to_array(self) -> [f32; 6] {
+        [self.camera.x(), self.camera.y(), self.camera.z(), self.rotation, self.horizon, self.fov]
+    }
+
+    fn from_array(time: f32, a: [f32; 6]) -> Self {
+        Keyframe {
+            time,
+            camera: Vec3::new(a[0], a[1], a[2]),
+            rotation: a[3],
+            horizon: a[4],
+            fov: a[5],
+        }
+    }
+}
+
+/// A list of keyframes, stored on disk as one whitespace separated
+/// `time x y z rotation horizon fov` line per keyframe.
+#[derive(Debug, Clone, Default)]
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl CameraPath {
+    pub fn load<R: BufRead>(reader: R) -> GameResult<Self> {
+        let mut keyframes = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse_error = |reason: &str| {
+                GameError::ResourceLoadError(format!(
+                    "camera path line {}: {} in {:?}",
+                    index + 1,
+                    reason,
+                    line
+                ))
+            };
+
+            let values = line
+                .split_whitespace()
+                .map(|value| value.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| parse_error(&err.to_string()))?;
+            if values.len() != 7 {
+                return Err(parse_error(&format!("expected 7 values, got {}", values.len())));
+            }
+
+            let mut pose = [0.0; 6];
+            pose.copy_from_slice(&values[1..]);
+            keyframes.push(Keyframe::from_array(values[0], pose));
+        }
+        Ok(CameraPath { keyframes })
+    }
+
+    pub fn save<W: Write>(&self, mut writer: W) -> GameResult {
+        writeln!(writer, "{}", PATH_HEADER)?;
+        writeln!(writer, "# time x y z rotation horizon fov")?;
+        for keyframe in &self.keyframes {
+            let [x, y, z, rotation, horizon, fov] = keyframe.to_array();
+            writeln!(
+                writer,
+                "{} {} {} {} {} {} {}",
+                keyframe.time, x, y, z, rotation, horizon, fov
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    /// Time between the first and last keyframe as recorded.
+    pub fn duration(&self) -> f32 {
+        match (self.keyframes.first(), self.keyframes.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.0,
+        }
+    }
+
+    /// Copy of the keyframes with rotations unwrapped, so that
+    /// interpolating between neighbours always turns the short way round.
+    fn unwrapped(&self) -> Vec<Keyframe> {
+        let full = 2.0 * PI;
+        let mut keyframes = self.keyframes.clone();
+        for i in 1..keyframes.len() {
+            let previous = keyframes[i - 1].rotation;
+            let mut rotation = keyframes[i].rotation;
+            while rotation - previous > PI {
+                rotation -= full;
+            }
+            while rotation - previous < -PI {
+                rotation += full;
+            }
+            keyframes[i].rotation = rotation;
+        }
+        keyframes
+    }
+}
+
+/// Samples the camera into a `CameraPath` at a fixed interval.
+#[derive(Debug, Default)]
+pub struct PathRecorder {
+    path: CameraPath,
+    elapsed: f32,
+    next_sample: f32,
+}
+
+impl PathRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, dt: f32, param: &MapDrawParam) {
+        if self.elapsed >= self.next_sample {
+            self.path.keyframes.push(Keyframe::new(self.elapsed, param));
+            self.next_sample += RECORD_INTERVAL;
+        }
+        self.elapsed += dt;
+    }
+
+    /// Stops recording, capturing one last keyframe where the camera is now.
+    pub fn finish(mut self, param: &MapDrawParam) -> CameraPath {
+        self.path.keyframes.push(Keyframe::new(self.elapsed, param));
+        self.path
+    }
+}
+
+/// How the curve between keyframes is built.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Passes through every keyframe
+    CatmullRom,
+    /// Uses the keyframes as control points of quadratic Bezier segments,
+    /// which is smoother but cuts corners
+    Bezier,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// How long playback takes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Timing {
+    /// Move along the path at this many units per second
+    Speed(f32),
+    /// Take this many seconds for the whole path
+    Duration(f32),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PathStyle {
+    pub interpolation: Interpolation,
+    pub easing: Easing,
+    pub timing: Timing,
+}
+
+/// Flies the camera along a `CameraPath`.
+#[derive(Debug)]
+pub struct PathPlayback {
+    keyframes: Vec<Keyframe>,
+    /// Distance along the path at each keyframe
+    distances: Vec<f32>,
+    style: PathStyle,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl PathPlayback {
+    /// Returns `None` if the path doesn't have any keyframes.
+    pub fn new(path: &CameraPath, style: PathStyle) -> Option<Self> {
+        if path.is_empty() {
+            return None;
+        }
+
+        let keyframes = path.unwrapped();
+        let mut distances = vec![0.0];
+        for pair in keyframes.windows(2) {
+            let last = distances[distances.len() - 1];
+            distances.push(last + (pair[1].camera - pair[0].camera).length());
+        }
+
+        let length = distances[distances.len() - 1];
+        let duration = match style.timing {
+            // a path that only turns on the spot keeps its recorded timing
+            Timing::Speed(_) if length <= 0.0 => path.duration(),
+            Timing::Speed(speed) => length / speed,
+            Timing::Duration(duration) => duration,
+        };
+
+        Some(PathPlayback { keyframes, distances, style, duration, elapsed: 0.0 })
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// Advances playback by `dt` seconds and poses the camera.
+    pub fn update(&mut self, dt: f32, param: &mut MapDrawParam) {
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+        self.sample(self.progress()).apply(param);
+    }
+
+    /// Eased progress through the path from 0 to 1.
+    fn progress(&self) -> f32 {
+        let t = if self.duration > 0.0 { self.elapsed / self.duration } else { 1.0 };
+        self.style.easing.apply(t.clamp(0.0, 1.0))
+    }
+
+    /// Pose at the given fraction of the path's length.
+    pub fn sample(&self, progress: f32) -> Keyframe {
+        let last = self.keyframes.len() - 1;
+        if last == 0 {
+            return self.keyframes[0];
+        }
+
+        let (segment, t) = self.locate(progress);
+        let at = |i: isize| self.keyframes[i.clamp(0, last as isize) as usize].to_array();
+        let i = segment as isize;
+        let time = self.keyframes[segment].time;
+        let mut pose = [0.0; 6];
+        match self.style.interpolation {
+            Interpolation::CatmullRom => {
+                let (p0, p1, p2, p3) = (at(i - 1), at(i), at(i + 1), at(i + 2));
+                for (k, value) in pose.iter_mut().enumerate() {
+                    *value = catmull_rom(p0[k], p1[k], p2[k], p3[k], t);
+                }
+            }
+            Interpolation::Bezier => {
+                // one piece per keyframe, running between the midpoints to
+                // its neighbours. the clamped ends start and stop exactly on
+                // the first and last keyframes
+                let pieces = (segment as f32 + t) / last as f32 * (last + 1) as f32;
+                let i = (pieces.floor() as isize).min(last as isize);
+                let t = pieces - i as f32;
+                let (p0, p1, p2) = (at(i - 1), at(i), at(i + 1));
+                for (k, value) in pose.iter_mut().enumerate() {
+                    let start = (p0[k] + p1[k]) / 2.0;
+                    let end = (p1[k] + p2[k]) / 2.0;
+                    *value = quadratic_bezier(start, p1[k], end, t);
+                }
+            }
+        }
+        Keyframe::from_array(time, pose)
+    }
+
+    /// Finds the segment and how far along it the given fraction of the path
+    /// is. Segments are measured by distance so the camera moves at an even
+    /// pace, unless the camera never moves and only turns.
+    fn locate(&self, progress: f32) -> (usize, f32) {
+        let last = self.keyframes.len() - 1;
+        let length = self.distances[last];
+        if length <= 0.0 {
+            let position = progress * last as f32;
+            let segment = (position.floor() as usize).min(last - 1);
+            return (segment, position - segment as f32);
+        }
+
+        let distance = progress * length;
+        let segment = match self.distances.iter().rposition(|&d| d <= distance) {
+            Some(i) => i.min(last - 1),
+            None => 0,
+        };
+        let segment_length = self.distances[segment + 1] - self.distances[segment];
+        let t = if segment_length > 0.0 {
+            ((distance - self.distances[segment]) / segment_length).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (segment, t)
+    }
+}
+
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+fn quadratic_bezier(p0: f32, p1: f32, p2: f32, t: f32) -> f32 {
+    let u = 1.0 - t;
+    u * u * p0 + 2.0 * u * t * p1 + t * t * p2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STYLE: PathStyle = PathStyle {
+        interpolation: Interpolation::CatmullRom,
+        easing: Easing::Linear,
+        timing: Timing::Duration(3.0),
+    };
+
+    fn keyframe(time: f32, x: f32, rotation: f32) -> Keyframe {
+        Keyframe { time, camera: Vec3::new(x, 100.0, -x), rotation, horizon: 80.0, fov: 1.2 }
+    }
+
+    fn path() -> CameraPath {
+        CameraPath {
+            keyframes: vec![
+                keyframe(0.0, 0.0, 0.0),
+                keyframe(0.5, 10.0, 0.5),
+                keyframe(1.0, 30.0, 1.0),
+                keyframe(1.5, 35.0, 1.25),
+            ],
+        }
+    }
+
+    fn assert_pose(actual: Keyframe, expected: Keyframe) {
+        let (actual, expected) = (actual.to_array(), expected.to_array());
+        for (a, e) in actual.iter().zip(&expected) {
+            assert!((a - e).abs() < 1e-3, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn paths_survive_a_round_trip() {
+        let path = path();
+        let mut file = Vec::new();
+        path.save(&mut file).unwrap();
+        let loaded = CameraPath::load(&file[..]).unwrap();
+
+        assert_eq!(loaded.keyframes.len(), path.keyframes.len());
+        for (loaded, saved) in loaded.keyframes.iter().zip(&path.keyframes) {
+            assert_eq!(loaded.time, saved.time);
+            assert_eq!(loaded.to_array(), saved.to_array());
+        }
+    }
+
+    #[test]
+    fn bad_lines_are_errors() {
+        let load = |file: &str| CameraPath::load(file.as_bytes());
+        assert!(load("0 1 2 3 0 100 1.2").is_ok());
+        assert!(load("0 1 2 3 0 100").is_err());
+        assert!(load("0 1 2 3 0 100 1.2 5").is_err());
+        assert!(load("0 1 2 north 0 100 1.2").is_err());
+    }
+
+    #[test]
+    fn catmull_rom_starts_and_ends_on_the_keyframes() {
+        let path = path();
+        let playback = PathPlayback::new(&path, STYLE).unwrap();
+        assert_pose(playback.sample(0.0), path.keyframes[0]);
+        assert_pose(playback.sample(1.0), path.keyframes[3]);
+
+        // and passes through the ones in between, by distance along the path
+        let length = playback.distances[3];
+        let progress = playback.distances[1] / length;
+        assert_pose(playback.sample(progress), path.keyframes[1]);
+    }
+
+    #[test]
+    fn rotations_turn_the_short_way_round() {
+        let path = CameraPath {
+            keyframes: vec![keyframe(0.0, 0.0, 2.0 * PI - 0.25), keyframe(1.0, 10.0, 0.25)],
+        };
+        let playback = PathPlayback::new(&path, STYLE).unwrap();
+        let rotation = playback.sample(0.5).rotation;
+        assert!((rotation - 2.0 * PI).abs() < 1e-3, "turned the long way to {}", rotation);
+    }
+
+    #[test]
+    fn empty_paths_dont_play() {
+        assert!(PathPlayback::new(&CameraPath::default(), STYLE).is_none());
+    }
+}
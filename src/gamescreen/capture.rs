@@ -0,0 +1,189 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use ggez::graphics;
+use ggez::Context;
+use ggez::GameError;
+use ggez::GameResult;
+
+use crate::cli::Args;
+
+pub const DEFAULT_CAPTURE_FPS: u32 = 60;
+
+pub const RECORD_USAGE: &str = "usage: voxelspace record --out DIR|FILE.y4m [--map N] \
+[--path FILE] [--fps N] [--size WIDTHxHEIGHT]";
+
+/// Settings for rendering a camera path to video, usually parsed from the
+/// command line.
+#[derive(Debug, Clone)]
+pub struct RecordConfig {
+    pub map_id: i32,
+    /// Directory for a PNG sequence, or a `.y4m` file
+    pub out: PathBuf,
+    /// Camera path to fly, or the last recorded one if `None`
+    pub path: Option<String>,
+    pub fps: u32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl RecordConfig {
+    /// Parses the arguments following `record`.
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> GameResult<Self> {
+        let mut args = Args::new(args, RECORD_USAGE);
+        let mut out = None;
+        let mut config = RecordConfig {
+            map_id: 1,
+            out: PathBuf::new(),
+            path: None,
+            fps: DEFAULT_CAPTURE_FPS,
+            width: 800.0,
+            height: 600.0,
+        };
+        while let Some(flag) = args.next_flag() {
+            match flag.as_str() {
+                "--out" => out = Some(PathBuf::from(args.string(&flag)?)),
+                "--map" => config.map_id = args.value(&flag)?,
+                "--path" => config.path = Some(args.string(&flag)?),
+                "--fps" => config.fps = args.value(&flag)?,
+                "--size" => {
+                    let (width, height) = args.size(&flag)?;
+                    config.width = width;
+                    config.height = height;
+                }
+                _ => return Err(args.error(&format!("unknown argument {}", flag))),
+            }
+        }
+
+        config.out = out.ok_or_else(|| args.error("--out is required"))?;
+        if config.fps == 0 {
+            return Err(args.error("--fps must be at least 1"));
+        }
+        Ok(config)
+    }
+}
+
+enum Sink {
+    /// Numbered PNG files in a directory
+    Png(PathBuf),
+    /// A single uncompressed YUV4MPEG2 stream
+    Y4m { writer: BufWriter<File>, size: Option<(u32, u32)> },
+}
+
+/// Writes every rendered frame out to disk.
+///
+/// While capturing, the simulation should advance by `frame_time` per frame
+/// instead of the real frame time, so slow frames don't show up as stutter
+/// in the video.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct FrameCapture {
+    #[derivative(Debug = "ignore")]
+    sink: Sink,
+    out: PathBuf,
+    fps: u32,
+    frames: usize,
+}
+
+impl FrameCapture {
+    /// Captures into a YUV4MPEG2 file if `out` ends in `.y4m`, and into a
+    /// directory of PNG files otherwise.
+    pub fn create(out: &Path, fps: u32) -> GameResult<Self> {
+        let sink = if out.extension() == Some(OsStr::new("y4m")) {
+            if let Some(parent) = out.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            Sink::Y4m { writer: BufWriter::new(File::create(out)?), size: None }
+        } else {
+            fs::create_dir_all(out)?;
+            Sink::Png(out.to_owned())
+        };
+        Ok(FrameCapture { sink, out: out.to_owned(), fps, frames: 0 })
+    }
+
+    /// Simulated seconds per frame.
+    pub fn frame_time(&self) -> f32 {
+        1.0 / self.fps as f32
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn out(&self) -> &Path {
+        &self.out
+    }
+
+    /// Grabs whatever has been drawn to the screen so far this frame.
+    pub fn write_frame(&mut self, ctx: &mut Context) -> GameResult {
+        let image = graphics::screenshot(ctx)?;
+        let (width, height) = (image.width() as u32, image.height() as u32);
+        let rgba = image.to_rgba8(ctx)?;
+
+        match &mut self.sink {
+            Sink::Png(dir) => {
+                let file = File::create(dir.join(format!("frame_{:06}.png", self.frames)))?;
+                let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                let mut writer = encoder.write_header().map_err(png_error)?;
+                writer.write_image_data(&rgba).map_err(png_error)?;
+            }
+            Sink::Y4m { writer, size } => {
+                match size {
+                    None => {
+                        writeln!(
+                            writer,
+                            "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                            width, height, self.fps
+                        )?;
+                        *size = Some((width, height));
+                    }
+                    Some(size) if *size != (width, height) => {
+                        return Err(GameError::RenderError(
+                            "the window can't be resized while capturing to y4m".to_owned(),
+                        ));
+                    }
+                    Some(_) => (),
+                }
+                writeln!(writer, "FRAME")?;
+                writer.write_all(&rgba_to_yuv444(&rgba))?;
+            }
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Flushes anything still buffered.
+    pub fn finish(mut self) -> GameResult {
+        if let Sink::Y4m { writer, .. } = &mut self.sink {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts to planar BT.601 studio range YCbCr, without chroma subsampling.
+fn rgba_to_yuv444(rgba: &[u8]) -> Vec<u8> {
+    let pixels = rgba.len() / 4;
+    let mut yuv = vec![0; pixels * 3];
+    let (y_plane, chroma) = yuv.split_at_mut(pixels);
+    let (u_plane, v_plane) = chroma.split_at_mut(pixels);
+    for (i, p) in rgba.chunks_exact(4).enumerate() {
+        let (r, g, b) = (p[0] as f32, p[1] as f32, p[2] as f32);
+        y_plane[i] = (16.0 + 0.257 * r + 0.504 * g + 0.098 * b).round() as u8;
+        u_plane[i] = (128.0 - 0.148 * r - 0.291 * g + 0.439 * b).round() as u8;
+        v_plane[i] = (128.0 + 0.439 * r - 0.368 * g - 0.071 * b).round() as u8;
+    }
+    yuv
+}
+
+fn png_error(err: png::EncodingError) -> GameError {
+    GameError::RenderError(format!("failed to encode frame: {}", err))
+}
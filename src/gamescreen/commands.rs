@@ -0,0 +1,222 @@
+//! The console commands of `MapState`.
+
+use ggez::graphics;
+use ggez::Context;
+use ggez::GameResult;
+use glam::*;
+
+use super::MapDrawParam;
+use super::MapState;
+use super::MovementMode;
+use super::Settings;
+use super::MAP_COUNT;
+use super::MAX_FOV;
+use super::MIN_FOV;
+use super::MIN_VIEW_DISTANCE;
+use super::TO_RADIANS;
+use crate::game::console::Command;
+use crate::game::World;
+
+/// View values that are typed in degrees but kept in radians
+const ANGLE_VALUES: &[&str] = &["rotation", "fov", "roll", "pitch"];
+const MOVEMENT_MODES: [MovementMode; 3] =
+    [MovementMode::Fly, MovementMode::Walk, MovementMode::Helicopter];
+
+impl MapState {
+    fn commands() -> Vec<Command> {
+        let mut values: Vec<String> = MapDrawParam::default()
+            .scalar_fields()
+            .into_iter()
+            .map(|(name, _)| name.to_owned())
+            .collect();
+        values.extend(Settings::default().values().into_iter().map(|(key, _)| key.to_owned()));
+        let modes = MOVEMENT_MODES.iter().map(|mode| mode.to_string().to_lowercase());
+
+        vec![
+            Command::new("teleport", "X Y Z", "Move the camera, Y being the height"),
+            Command::new("set", "NAME [VALUE]", "Show or change a view value or setting")
+                .with_completions(values),
+            Command::new("map", "N", "Load a map")
+                .with_completions((1..=MAP_COUNT).map(|id| id.to_string())),
+            Command::new("record", "start|stop", "Record a camera path")
+                .with_completions(vec!["start".to_owned(), "stop".to_owned()]),
+            Command::new("movement", "fly|walk|helicopter", "Switch how the camera moves")
+                .with_completions(modes),
+            Command::new("screenshot", "", "Save a screenshot"),
+            Command::new("bookmark", "save|go|delete NAME, or list", "Manage saved views")
+                .with_completions(
+                    vec!["save", "go", "delete", "list"].into_iter().map(str::to_owned),
+                ),
+        ]
+    }
+
+    pub(super) fn register_commands(world: &mut World) {
+        for command in Self::commands() {
+            world.commands.register(command);
+        }
+    }
+
+    pub(super) fn unregister_commands(world: &mut World) {
+        for command in Self::commands() {
+            world.commands.unregister(command.name);
+        }
+    }
+
+    pub(super) fn run_command(
+        &mut self,
+        ctx: &mut Context,
+        world: &mut World,
+        name: &str,
+        args: &[&str],
+    ) -> GameResult<Option<String>> {
+        // like `set`, anything that isn't a finite number is refused
+        let coordinate = |value: &str| value.parse::<f32>().ok().filter(|value| value.is_finite());
+        let output = match (name, args) {
+            ("teleport", [x, y, z]) => match (coordinate(x), coordinate(y), coordinate(z)) {
+                (Some(x), Some(y), Some(z)) => {
+                    self.draw_param.camera = Vec3::new(x, y, z);
+                    self.ground_follow = None;
+                    self.stop();
+                    format!("Teleported to {} {} {}", x, y, z)
+                }
+                _ => world.commands.usage(name),
+            },
+            ("set", [key]) => self.set_value(ctx, world, key, None)?,
+            ("set", [key, value]) => self.set_value(ctx, world, key, Some(value))?,
+            ("map", [id]) => match id.parse() {
+                Ok(map_id) if (1..=MAP_COUNT).contains(&map_id) => {
+                    self.load_map(ctx, map_id)?;
+                    format!("Loaded map {}", map_id)
+                }
+                _ => format!("no map {}, there are {}", id, MAP_COUNT),
+            },
+            ("record", ["start"]) if self.recorder.is_none() => {
+                self.toggle_recording(ctx)?;
+                "Recording the camera path".to_owned()
+            }
+            ("record", ["stop"]) if self.recorder.is_some() => {
+                self.toggle_recording(ctx)?;
+                "Camera path saved".to_owned()
+            }
+            ("record", ["start"]) => "Already recording".to_owned(),
+            ("record", ["stop"]) => "Not recording".to_owned(),
+            ("movement", [mode]) => {
+                match MOVEMENT_MODES.iter().find(|m| m.to_string().eq_ignore_ascii_case(mode)) {
+                    Some(&mode) => {
+                        self.movement = mode;
+                        self.stop();
+                        format!("Movement is {}", mode)
+                    }
+                    None => world.commands.usage(name),
+                }
+            }
+            ("screenshot", []) => {
+                self.screenshot_requested = true;
+                "Saving a screenshot".to_owned()
+            }
+            ("bookmark", ["save", name]) => {
+                self.save_bookmark(ctx, name)?;
+                format!("Saved bookmark {}", name)
+            }
+            ("bookmark", ["go", name]) => match self.bookmarks.find(self.map_id, name).cloned() {
+                Some(bookmark) => {
+                    self.go_to_bookmark(ctx, &bookmark)?;
+                    format!("Bookmark {} on map {}", bookmark.name, bookmark.map_id)
+                }
+                None => format!("no bookmark {}", name),
+            },
+            ("bookmark", ["delete", name]) => {
+                if self.delete_bookmark(ctx, name)? {
+                    format!("Deleted bookmark {}", name)
+                } else {
+                    format!("no bookmark {} on map {}", name, self.map_id)
+                }
+            }
+            ("bookmark", ["list"]) => {
+                let names: Vec<&str> = self
+                    .bookmarks
+                    .on_map(self.map_id)
+                    .into_iter()
+                    .map(|bookmark| bookmark.name.as_str())
+                    .collect();
+                if names.is_empty() {
+                    format!("No bookmarks on map {}", self.map_id)
+                } else {
+                    format!("Bookmarks on map {}: {}", self.map_id, names.join(" "))
+                }
+            }
+            _ if Self::commands().iter().any(|command| command.name == name) => {
+                world.commands.usage(name)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(output))
+    }
+
+    /// Shows or changes a value of the view, or failing that one of the
+    /// settings.
+    fn set_value(
+        &mut self,
+        ctx: &mut Context,
+        world: &mut World,
+        key: &str,
+        value: Option<&str>,
+    ) -> GameResult<String> {
+        let scale = if ANGLE_VALUES.contains(&key) { TO_RADIANS } else { 1.0 };
+        let mut param = self.draw_param.clone();
+        let field = param.scalar_fields().into_iter().find(|(name, _)| *name == key);
+        if let Some((_, field)) = field {
+            let value = match value {
+                Some(value) => value,
+                None => return Ok(format!("{} = {}", key, *field / scale)),
+            };
+            let number = match value.parse::<f32>() {
+                Ok(number) if number.is_finite() => number * scale,
+                _ => return Ok(format!("invalid value {:?} for {}", value, key)),
+            };
+            if let Some(reason) = out_of_range(key, number) {
+                return Ok(reason);
+            }
+            *field = number;
+            self.draw_param = param;
+            // a typed value is a jump, not something to smooth over
+            self.previous_param = self.draw_param.clone();
+            return Ok(format!("{} = {}", key, value));
+        }
+
+        let mut settings = self.current_settings(ctx, world);
+        let current = settings.values().into_iter().find(|(name, _)| *name == key);
+        match (current, value) {
+            (None, _) => Ok(format!("unknown value {}", key)),
+            (Some((_, current)), None) => Ok(format!("{} = {}", key, current)),
+            (Some(_), Some(value)) => {
+                if let Err(reason) = settings.set(key, value) {
+                    return Ok(reason);
+                }
+                self.apply_settings(ctx, &settings)?;
+                world.tick_rate = settings.tick_rate;
+                world.input.analog = settings.analog;
+                if graphics::drawable_size(ctx) != (settings.width, settings.height) {
+                    graphics::set_drawable_size(ctx, settings.width, settings.height)?;
+                }
+                Ok(format!("{} = {}", key, value))
+            }
+        }
+    }
+}
+
+/// Why a view value can't be set to `value`, if it can't.
+fn out_of_range(key: &str, value: f32) -> Option<String> {
+    match key {
+        "fov" if !(MIN_FOV..=MAX_FOV).contains(&value) => Some(format!(
+            "fov must be between {:.0} and {:.0}",
+            MIN_FOV / TO_RADIANS,
+            MAX_FOV / TO_RADIANS
+        )),
+        "view_distance" if value < MIN_VIEW_DISTANCE => {
+            Some(format!("view_distance must be at least {}", MIN_VIEW_DISTANCE))
+        }
+        "detail" if value <= 0.0 => Some("detail must be more than 0".to_owned()),
+        _ => None,
+    }
+}
@@ -0,0 +1,254 @@
+use ggez::event::KeyCode;
+use ggez::graphics;
+use ggez::graphics::Color;
+use ggez::graphics::DrawMode;
+use ggez::graphics::DrawParam;
+use ggez::graphics::Font;
+use ggez::graphics::Mesh;
+use ggez::graphics::Rect;
+use ggez::graphics::Text;
+use ggez::graphics::TextFragment;
+use ggez::Context;
+use ggez::GameResult;
+use glam::*;
+
+use crate::game::console::Command;
+use crate::game::input::types::InputEffect;
+use crate::game::input::types::InputType;
+use crate::game::input::Button;
+use crate::game::input::Event;
+use crate::game::Scene;
+use crate::game::Transition;
+use crate::game::World;
+
+const BACKDROP: Color = Color { r: 0.02, g: 0.03, b: 0.04, a: 0.85 };
+const TEXT: Color = Color { r: 0.85, g: 0.85, b: 0.85, a: 1.0 };
+const PROMPT: Color = Color { r: 1.0, g: 0.85, b: 0.3, a: 1.0 };
+const FONT_SIZE: f32 = 14.0;
+/// Height of a line of text, for working out how many fit
+const LINE_HEIGHT: f32 = 17.0;
+/// Fraction of the window the console covers
+const CONSOLE_HEIGHT: f32 = 0.4;
+const MARGIN: f32 = 8.0;
+
+/// A drop-down console over the top of the screen for typing commands.
+/// Lines are handed to `World::commands` to run, and whatever the commands
+/// print shows above the prompt. Its own commands are `help` and `clear`.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct Console {
+    #[derivative(Debug = "ignore")]
+    font: Font,
+    line: String,
+    /// Position in the history while going back through it with the arrow
+    /// keys
+    browsing: Option<usize>,
+    /// Nothing has updated since the console opened, so any text typed is
+    /// from the key that opened it
+    just_opened: bool,
+    closed: bool,
+}
+
+impl Console {
+    pub fn new(ctx: &mut Context) -> GameResult<Self> {
+        Ok(Console {
+            font: Font::new(ctx, "/LiberationMono-Regular.ttf")?,
+            line: String::new(),
+            browsing: None,
+            just_opened: true,
+            closed: false,
+        })
+    }
+
+    /// Steps through the history, `back` towards older lines.
+    fn browse(&mut self, state: &World, back: bool) {
+        let history = state.commands.history();
+        self.browsing = match (self.browsing, back) {
+            (None, true) if !history.is_empty() => Some(history.len() - 1),
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index + 1 < history.len() => Some(index + 1),
+            (browsing, _) => {
+                if browsing.is_some() {
+                    self.line.clear();
+                }
+                None
+            }
+        };
+        if let Some(index) = self.browsing {
+            self.line = history[index].clone();
+        }
+    }
+
+    /// Completes the word being typed as far as it's unambiguous, listing
+    /// the options when there are several.
+    fn complete(&mut self, state: &mut World) {
+        let candidates = state.commands.complete(&self.line);
+        let prefix = match candidates.split_first() {
+            Some((first, rest)) => rest.iter().fold(first.as_str(), |prefix, candidate| {
+                let common = prefix.chars().zip(candidate.chars()).take_while(|(a, b)| a == b);
+                &prefix[..common.map(|(a, _)| a.len_utf8()).sum::<usize>()]
+            }),
+            None => return,
+        };
+
+        let start = self.line.rfind(char::is_whitespace).map_or(0, |index| index + 1);
+        self.line.truncate(start);
+        self.line.push_str(prefix);
+        if candidates.len() == 1 {
+            self.line.push(' ');
+        } else {
+            state.commands.print(&candidates.join("  "));
+        }
+    }
+}
+
+impl Scene for Console {
+    fn update(&mut self, _state: &mut World, _ctx: &mut Context) -> GameResult<Transition> {
+        self.just_opened = false;
+        Ok(if self.closed { Transition::Pop } else { Transition::None })
+    }
+
+    fn draw(&mut self, state: &mut World, ctx: &mut Context) -> GameResult {
+        let (width, height) = graphics::drawable_size(ctx);
+        let console_height = height * CONSOLE_HEIGHT;
+        let backdrop = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, width, console_height),
+            BACKDROP,
+        )?;
+        graphics::draw(ctx, &backdrop, DrawParam::new())?;
+
+        let fragment = |text: String, color: Color| {
+            TextFragment::new(text).color(color).font(self.font).scale(FONT_SIZE)
+        };
+
+        // the newest output that fits above the prompt
+        let fits = ((console_height - 2.0 * MARGIN) / LINE_HEIGHT) as usize;
+        let log = state.commands.log();
+        let shown = &log[log.len() - log.len().min(fits.saturating_sub(1))..];
+
+        let mut text = Text::default();
+        for line in shown {
+            text.add(fragment(format!("{}\n", line), TEXT));
+        }
+        text.add(fragment(format!("> {}_", self.line), PROMPT));
+        let top = console_height - MARGIN - (shown.len() + 1) as f32 * LINE_HEIGHT;
+        graphics::draw(ctx, &text, (Vec2::new(MARGIN, top),))?;
+        Ok(())
+    }
+
+    fn input(
+        &mut self,
+        _state: &mut World,
+        _ctx: &mut Context,
+        _event: Event,
+        _started: bool,
+    ) -> GameResult<Transition> {
+        Ok(Transition::None)
+    }
+
+    /// Takes every input, so typing doesn't move the camera. Whatever opens
+    /// the console closes it again, as does Escape.
+    fn raw_input(
+        &mut self,
+        state: &mut World,
+        ctx: &mut Context,
+        input: InputType,
+        started: bool,
+    ) -> GameResult<bool> {
+        if !started {
+            return Ok(true);
+        }
+        let mods = ggez::input::keyboard::active_mods(ctx);
+        if state.binding.resolve(input, mods) == Some(InputEffect::Button(Button::Console)) {
+            self.closed = true;
+            return Ok(true);
+        }
+
+        let key = match input {
+            InputType::KeyEvent(key) => key,
+            _ => return Ok(true),
+        };
+        match key {
+            KeyCode::Escape => self.closed = true,
+            KeyCode::Return | KeyCode::NumpadEnter => {
+                state.commands.submit(&self.line);
+                self.line.clear();
+                self.browsing = None;
+            }
+            KeyCode::Back => {
+                self.line.pop();
+            }
+            KeyCode::Up => self.browse(state, true),
+            KeyCode::Down => self.browse(state, false),
+            KeyCode::Tab => self.complete(state),
+            _ => (),
+        }
+        Ok(true)
+    }
+
+    fn text_input(
+        &mut self,
+        _state: &mut World,
+        _ctx: &mut Context,
+        character: char,
+    ) -> GameResult {
+        if !character.is_control() && !self.just_opened && !self.closed {
+            self.line.push(character);
+        }
+        Ok(())
+    }
+
+    fn command(
+        &mut self,
+        state: &mut World,
+        _ctx: &mut Context,
+        name: &str,
+        args: &[&str],
+    ) -> GameResult<Option<String>> {
+        let output = match (name, args) {
+            ("help", []) => state
+                .commands
+                .all()
+                .iter()
+                .map(|command| format!("{:<32}{}", command.usage_text(), command.help))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ("help", [name]) => match state.commands.get(name) {
+                Some(command) => format!("{}\n{}", command.usage_text(), command.help),
+                None => format!("unknown command {}", name),
+            },
+            ("clear", []) => {
+                state.commands.clear_log();
+                String::new()
+            }
+            ("help", _) | ("clear", _) => state.commands.usage(name),
+            _ => return Ok(None),
+        };
+        Ok(Some(output))
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+
+    fn on_enter(&mut self, state: &mut World, _ctx: &mut Context) -> GameResult {
+        let mut names: Vec<String> =
+            state.commands.all().iter().map(|command| command.name.to_owned()).collect();
+        names.extend(vec!["help".to_owned(), "clear".to_owned()]);
+        let help = Command::new("help", "[COMMAND]", "List the commands, or explain one")
+            .with_completions(names);
+        state.commands.register(help);
+        state.commands.register(Command::new("clear", "", "Clear the console"));
+        Ok(())
+    }
+
+    /// Keys held when the console closes were let go of while it was open.
+    fn on_exit(&mut self, state: &mut World, _ctx: &mut Context) -> GameResult {
+        state.commands.unregister("help");
+        state.commands.unregister("clear");
+        state.input.reset_input_state();
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use ggez::event::KeyCode;
+use ggez::graphics;
+use ggez::graphics::Align;
+use ggez::graphics::Color;
+use ggez::graphics::Font;
+use ggez::graphics::Text;
+use ggez::graphics::TextFragment;
+use ggez::Context;
+use ggez::GameResult;
+use glam::*;
+
+use crate::game::input::controls;
+use crate::game::input::create_input_binding;
+use crate::game::input::types::Chord;
+use crate::game::input::types::InputEffect;
+use crate::game::input::types::InputType;
+use crate::game::input::Event;
+use crate::game::Scene;
+use crate::game::Transition;
+use crate::game::World;
+
+const BACKGROUND: Color = Color { r: 0.08, g: 0.1, b: 0.12, a: 1.0 };
+const TEXT: Color = Color { r: 0.85, g: 0.85, b: 0.85, a: 1.0 };
+const SELECTED: Color = Color { r: 1.0, g: 0.85, b: 0.3, a: 1.0 };
+const WARNING: Color = Color { r: 1.0, g: 0.4, b: 0.35, a: 1.0 };
+const FONT_SIZE: f32 = 16.0;
+const INSTRUCTIONS: &str = "↑↓: Select  Enter: Rebind  A: Add Key  Backspace: Clear  \
+R: Reset All  Escape: Save and Close";
+/// Keys that make chords with the next key, unless they're let go of alone
+const MODIFIER_KEYS: &[KeyCode] = &[
+    KeyCode::LShift,
+    KeyCode::RShift,
+    KeyCode::LControl,
+    KeyCode::RControl,
+    KeyCode::LAlt,
+    KeyCode::RAlt,
+    KeyCode::LWin,
+    KeyCode::RWin,
+];
+
+/// What the next key press does while the menu waits for one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Rebind {
+    /// Becomes the only input for the action
+    Replace,
+    /// Joins the inputs already bound to the action
+    Add,
+}
+
+/// Lists every action with the inputs bound to it and lets the player
+/// change them. Edits go straight into `World::binding` and are saved to
+/// the controls file when the menu is closed, which pops it off the scene
+/// stack.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct ControlsMenu {
+    #[derivative(Debug = "ignore")]
+    font: Font,
+    actions: Vec<Event>,
+    selected: usize,
+    rebind: Option<Rebind>,
+    /// Modifier pressed while rebinding, which is bound by itself if it's
+    /// released before another key is pressed
+    modifier: Option<KeyCode>,
+    /// What happened last, and whether it needs the player's attention
+    status: Option<(String, bool)>,
+    closed: bool,
+}
+
+impl ControlsMenu {
+    pub fn new(ctx: &mut Context) -> GameResult<Self> {
+        Ok(ControlsMenu {
+            font: Font::new(ctx, "/LiberationMono-Regular.ttf")?,
+            actions: controls::actions(),
+            selected: 0,
+            rebind: None,
+            modifier: None,
+            status: None,
+            closed: false,
+        })
+    }
+
+    fn bind(&mut self, world: &mut World, chord: Chord, rebind: Rebind) {
+        self.rebind = None;
+        self.modifier = None;
+        let action = self.actions[self.selected];
+        let previous = world.binding.bound_to(chord);
+        if rebind == Rebind::Replace {
+            world.binding.unbind(&action);
+        }
+        world.binding.bind(chord, action);
+
+        let input = controls::chord_name(chord);
+        self.status = match previous {
+            // an input can only do one thing, so it's taken from the other action
+            Some(previous) if previous != action => {
+                Some((format!("{} was bound to {}, not anymore", input, label(&previous)), true))
+            }
+            _ => Some((format!("{} bound to {}", input, label(&action)), false)),
+        };
+    }
+
+    fn navigate(&mut self, world: &mut World, ctx: &mut Context, key: KeyCode) -> GameResult {
+        let count = self.actions.len();
+        match key {
+            KeyCode::Up => self.selected = (self.selected + count - 1) % count,
+            KeyCode::Down => self.selected = (self.selected + 1) % count,
+            KeyCode::Return => self.rebind = Some(Rebind::Replace),
+            KeyCode::A => self.rebind = Some(Rebind::Add),
+            KeyCode::Back => {
+                let action = self.actions[self.selected];
+                world.binding.unbind(&action);
+                self.status = Some((format!("{} cleared", label(&action)), false));
+            }
+            KeyCode::R => {
+                world.binding = create_input_binding();
+                self.status = Some(("Reset to the default controls".to_owned(), false));
+            }
+            KeyCode::Escape => {
+                controls::save_controls(ctx, &world.binding)?;
+                self.closed = true;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl Scene for ControlsMenu {
+    fn update(&mut self, _state: &mut World, _ctx: &mut Context) -> GameResult<Transition> {
+        Ok(if self.closed { Transition::Pop } else { Transition::None })
+    }
+
+    fn draw(&mut self, state: &mut World, ctx: &mut Context) -> GameResult {
+        graphics::clear(ctx, BACKGROUND);
+
+        let fragment = |text: String, color: Color| {
+            TextFragment::new(text).color(color).font(self.font).scale(FONT_SIZE)
+        };
+
+        let mut text = Text::default();
+        text.add(fragment(format!("Controls\n{}\n\n", INSTRUCTIONS), TEXT));
+        for (i, action) in self.actions.iter().enumerate() {
+            let inputs = controls::inputs_text(&state.binding, action);
+            let color = if i == self.selected {
+                SELECTED
+            } else if state.binding.inputs_for(action).is_empty() {
+                WARNING
+            } else {
+                TEXT
+            };
+            let marker = if i == self.selected { '>' } else { ' ' };
+            text.add(fragment(format!("{} {:<32}{}\n", marker, label(action), inputs), color));
+        }
+
+        let status = match (self.rebind, &self.status) {
+            (Some(_), _) => Some((
+                format!(
+                    "Press a key or mouse button for {}, holding any modifiers, Escape to cancel",
+                    label(&self.actions[self.selected])
+                ),
+                false,
+            )),
+            (None, status) => status.clone(),
+        };
+        if let Some((status, warning)) = status {
+            text.add(fragment(format!("\n{}", status), if warning { WARNING } else { TEXT }));
+        }
+
+        let (width, height) = graphics::drawable_size(ctx);
+        text.set_bounds(Vec2::new(width, height), Align::Left);
+        graphics::draw(ctx, &text, (Vec2::new(20.0, 20.0),))?;
+        Ok(())
+    }
+
+    fn input(
+        &mut self,
+        _state: &mut World,
+        _ctx: &mut Context,
+        _event: Event,
+        _started: bool,
+    ) -> GameResult<Transition> {
+        Ok(Transition::None)
+    }
+
+    /// Takes every input, the bindings don't apply while they're being edited.
+    fn raw_input(
+        &mut self,
+        state: &mut World,
+        ctx: &mut Context,
+        input: InputType,
+        started: bool,
+    ) -> GameResult<bool> {
+        let rebind = match self.rebind {
+            Some(rebind) => rebind,
+            None => {
+                if let (true, InputType::KeyEvent(key)) = (started, input) {
+                    self.navigate(state, ctx, key)?;
+                }
+                return Ok(true);
+            }
+        };
+
+        match input {
+            InputType::KeyEvent(KeyCode::Escape) if started => {
+                self.rebind = None;
+                self.modifier = None;
+                self.status = None;
+            }
+            InputType::KeyEvent(key) if MODIFIER_KEYS.contains(&key) => {
+                if started {
+                    self.modifier = Some(key);
+                } else if self.modifier == Some(key) {
+                    self.bind(state, input.into(), rebind);
+                }
+            }
+            input if started => {
+                let mods = ggez::input::keyboard::active_mods(ctx);
+                self.bind(state, Chord::new(mods, input), rebind);
+            }
+            _ => (),
+        }
+        Ok(true)
+    }
+
+    /// Keys held when the menu closes were let go of while it was open.
+    fn on_exit(&mut self, state: &mut World, _ctx: &mut Context) -> GameResult {
+        state.input.reset_input_state();
+        Ok(())
+    }
+}
+
+/// How an action is shown in the menu.
+fn label(action: &Event) -> String {
+    match action {
+        InputEffect::Axis(axis, true) => format!("{} +", axis.description()),
+        InputEffect::Axis(axis, false) => format!("{} -", axis.description()),
+        InputEffect::Button(button) => button.description().to_owned(),
+        InputEffect::Pointer(..) => controls::action_name(action),
+    }
+}
@@ -6,6 +6,12 @@ use ggez::GameResult;
 use glam::*;
 
 use super::MapDrawParam;
+use super::Pick;
+use crate::game::input::controls;
+use crate::game::input::Binding;
+
+/// Pixels between a notice and the bottom of the screen
+const NOTICE_MARGIN: f32 = 40.0;
 
 #[derive(Derivative)]
 #[derivative(Debug)]
@@ -19,13 +25,33 @@ impl DebugText {
         Ok(DebugText { font: Font::new(ctx, "/LiberationMono-Regular.ttf")? })
     }
 
-    pub fn draw(&mut self, param: &MapDrawParam, map_id: i32, ctx: &mut Context) -> GameResult {
+    pub fn draw(
+        &mut self,
+        param: &MapDrawParam,
+        map_id: i32,
+        mode: &str,
+        cursor: Option<Pick>,
+        binding: &Binding,
+        ctx: &mut Context,
+    ) -> GameResult {
+        let cursor = match cursor {
+            Some(pick) => format!(
+                "({x:.0}, {z:.0}) Elevation: {y:.0} Distance: {distance:.0}",
+                x = pick.point.x(),
+                y = pick.point.y(),
+                z = pick.point.z(),
+                distance = pick.distance,
+            ),
+            None => "Sky".to_owned(),
+        };
+
         let left = format!(
             "FPS: {fps:.0}
 Position: ({x:.0}, {y:.0}, {z:.0}) {rotation:.0}°
 Render Distance: {view_distance:.0} FOV: {fov:.0}°
 Height Scale: {height_scale:.0} Horizon: {horizon:.0}
-Map: {map_id}",
+Map: {map_id} Mode: {mode}
+Cursor: {cursor}",
             fps = ggez::timer::fps(ctx),
             x = param.camera.x(),
             y = param.camera.y(),
@@ -36,17 +62,11 @@ Map: {map_id}",
             height_scale = param.height_scale,
             horizon = param.horizon,
             map_id = map_id,
+            mode = mode,
+            cursor = cursor,
         );
 
-        let right = "Controls:
-WASD: Move Camera
-←→: Change Render Distance
-↑↓: Change Height Scale
-Space/LShift: Move Up/Down
-[]: Change FOV 
-N/P: Next/Previous Map
-L: Toggle Debug Text
-Drag the screen to pan/rotate the camera";
+        let right = controls::help_text(binding);
 
         let (width, height) = ggez::graphics::drawable_size(ctx);
         let bounds = Vec2::new(width, height);
@@ -60,4 +80,14 @@ Drag the screen to pan/rotate the camera";
         ggez::graphics::draw(ctx, &right_text, (Vec2::new(0.0, 0.0),))?;
         Ok(())
     }
+
+    /// Draws a short message centred near the bottom of the screen, shown
+    /// whether or not the rest of the debug text is.
+    pub fn draw_notice(&mut self, notice: &str, ctx: &mut Context) -> GameResult {
+        let (width, height) = ggez::graphics::drawable_size(ctx);
+        let mut text = Text::new((notice.to_owned(), self.font, 16.0));
+        text.set_bounds(Vec2::new(width, height), Align::Center);
+        ggez::graphics::draw(ctx, &text, (Vec2::new(0.0, height - NOTICE_MARGIN),))?;
+        Ok(())
+    }
 }
@@ -0,0 +1,86 @@
+use std::io::Write;
+
+use ggez::GameError;
+use ggez::GameResult;
+
+/// Per-pixel world distance of everything the renderer drew last frame.
+///
+/// Distances are measured along the ray the renderer marched for that
+/// screen column, the same `z` that `Map::draw` steps through. Pixels
+/// that only show sky hold `f32::INFINITY`. The buffer is laid out
+/// before camera roll is applied.
+#[derive(Derivative, Default)]
+#[derivative(Debug)]
+pub struct DepthBuffer {
+    width: usize,
+    height: usize,
+    #[derivative(Debug = "ignore")]
+    data: Vec<f32>,
+}
+
+impl DepthBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Resizes the buffer to the given screen size and marks every pixel as sky.
+    pub fn reset(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.data.clear();
+        self.data.resize(width * height, f32::INFINITY);
+    }
+
+    /// Distance to whatever is drawn at the given pixel, or `None` for sky
+    /// and for pixels outside of the buffer.
+    pub fn get(&self, x: usize, y: usize) -> Option<f32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let depth = self.data[x + y * self.width];
+        if depth.is_finite() {
+            Some(depth)
+        } else {
+            None
+        }
+    }
+
+    /// Writes `depth` into column `x` for every row in `y1..y2`.
+    pub fn fill_span(&mut self, x: usize, y1: usize, y2: usize, depth: f32) {
+        let y2 = y2.min(self.height);
+        for y in y1..y2 {
+            self.data[x + y * self.width] = depth;
+        }
+    }
+
+    /// Encodes the buffer as a 16-bit grayscale PNG, mapping distances
+    /// `0..max_distance` to `0..65535`. Sky is written as `65535`.
+    pub fn write_png<W: Write>(&self, writer: W, max_distance: f32) -> GameResult {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Sixteen);
+
+        // png wants 16-bit samples in big endian order
+        let mut bytes = Vec::with_capacity(self.data.len() * 2);
+        for depth in &self.data {
+            let scaled = (depth / max_distance).clamp(0.0, 1.0) * u16::MAX as f32;
+            bytes.extend_from_slice(&(scaled as u16).to_be_bytes());
+        }
+
+        let mut png_writer = encoder.write_header().map_err(png_error)?;
+        png_writer.write_image_data(&bytes).map_err(png_error)?;
+        Ok(())
+    }
+}
+
+fn png_error(err: png::EncodingError) -> GameError {
+    GameError::RenderError(format!("failed to encode depth buffer: {}", err))
+}
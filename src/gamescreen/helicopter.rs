@@ -0,0 +1,124 @@
+use std::f32::consts::PI;
+
+use glam::*;
+
+use super::map::Map;
+use super::MapDrawParam;
+use super::TO_RADIANS;
+use crate::game::input::Axis;
+use crate::game::input::State;
+
+const DEFAULT_ACCELERATION: f32 = 150.0;
+const DEFAULT_LIFT: f32 = 150.0;
+const DEFAULT_DRAG: f32 = 2.0;
+const DEFAULT_YAW_RATE: f32 = 60.0 * TO_RADIANS;
+const DEFAULT_MAX_BANK: f32 = 15.0 * TO_RADIANS;
+const DEFAULT_MAX_PITCH: f32 = 8.0 * TO_RADIANS;
+const DEFAULT_TILT_RATE: f32 = 3.0;
+
+/// Comanche style flight model with momentum.
+///
+/// Throttle tilts the rotor to push the helicopter forwards or backwards,
+/// strafe yaws it and height works the collective. Top speeds fall out of
+/// `acceleration`, `lift` and `drag` rather than `MapState::speed` and
+/// `MapState::h_speed`.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct Helicopter {
+    /// Horizontal acceleration at full throttle
+    pub acceleration: f32,
+    /// Vertical acceleration at full collective
+    pub lift: f32,
+    /// Damping rate per second. Without thrust the velocity falls by a
+    /// factor of e every `1 / drag` seconds, and top speed is about the
+    /// acceleration over the drag
+    pub drag: f32,
+    /// Turning speed in radians per second at full strafe
+    pub yaw_rate: f32,
+    /// Roll when turning at full rate
+    pub max_bank: f32,
+    /// Nose down pitch at full throttle
+    pub max_pitch: f32,
+    /// How quickly bank and pitch follow the controls
+    pub tilt_rate: f32,
+    #[derivative(Debug(format_with = "super::vec3_fmt"))]
+    velocity: Vec3,
+    bank: f32,
+    pitch: f32,
+}
+
+impl Default for Helicopter {
+    fn default() -> Self {
+        Helicopter {
+            acceleration: DEFAULT_ACCELERATION,
+            lift: DEFAULT_LIFT,
+            drag: DEFAULT_DRAG,
+            yaw_rate: DEFAULT_YAW_RATE,
+            max_bank: DEFAULT_MAX_BANK,
+            max_pitch: DEFAULT_MAX_PITCH,
+            tilt_rate: DEFAULT_TILT_RATE,
+            velocity: Vec3::zero(),
+            bank: 0.0,
+            pitch: 0.0,
+        }
+    }
+}
+
+impl Helicopter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    /// Kills all momentum and levels out.
+    pub fn stop(&mut self) {
+        self.velocity = Vec3::zero();
+        self.bank = 0.0;
+        self.pitch = 0.0;
+    }
+
+    /// Integrates one step of flight and writes the resulting position,
+    /// heading, roll and pitch into `param`. The helicopter is kept at least
+    /// `clearance` above the terrain.
+    pub fn update(
+        &mut self,
+        map: &Map,
+        param: &mut MapDrawParam,
+        input: &State,
+        clearance: f32,
+        dt: f32,
+    ) {
+        let throttle = input.get_axis(Axis::Throttle);
+        let turn = input.get_axis(Axis::Strafe);
+        let collective = input.get_axis(Axis::Height);
+
+        // positive strafe is to the right, which is a decreasing angle
+        let full = 2.0 * PI;
+        param.rotation -= turn * self.yaw_rate * dt;
+        param.rotation = (param.rotation % full + full) % full;
+
+        let forward = Vec3::new(param.rotation.cos(), 0.0, param.rotation.sin());
+        let thrust =
+            forward * throttle * self.acceleration + Vec3::new(0.0, collective * self.lift, 0.0);
+        // decaying exponentially rather than subtracting a step of drag, which
+        // would overshoot and oscillate once `drag * dt` goes past 1
+        self.velocity = self.velocity * (-self.drag * dt).exp() + thrust * dt;
+        param.camera += self.velocity * dt;
+
+        let ground = map.height_at(param.camera.x(), param.camera.z()) + clearance;
+        if param.camera.y() < ground {
+            param.camera.set_y(ground);
+            *self.velocity.y_mut() = self.velocity.y().max(0.0);
+        }
+
+        // lean into turns and dip the nose when pushing forwards
+        let tilt = (self.tilt_rate * dt).min(1.0);
+        self.bank += (turn * self.max_bank - self.bank) * tilt;
+        self.pitch += (-throttle * self.max_pitch - self.pitch) * tilt;
+        param.roll = self.bank;
+        param.pitch = self.pitch;
+    }
+}
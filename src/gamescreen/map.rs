@@ -1,15 +1,46 @@
+use std::io::Read;
+
 use ggez::graphics;
 use ggez::graphics::spritebatch::SpriteBatch;
 use ggez::graphics::Color;
 use ggez::graphics::DrawParam;
 use ggez::graphics::Image;
 use ggez::Context;
+use ggez::GameError;
 use ggez::GameResult;
 use glam::*;
 
-const RENDER_DETAIL: f32 = 150.0;
+/// Brightness of the lowest terrain in thumbnails, the highest is full
+const THUMBNAIL_SHADE: f32 = 0.45;
 
+use super::DepthBuffer;
 use super::MapDrawParam;
+
+/// The terrain point underneath a screen pixel.
+#[derive(Derivative, Copy, Clone)]
+#[derivative(Debug)]
+pub struct Pick {
+    /// World position of the point, laid out like `MapDrawParam::camera`
+    #[derivative(Debug(format_with = "super::vec3_fmt"))]
+    pub point: Vec3,
+    /// Distance from the camera along the ray to the point
+    pub distance: f32,
+}
+
+/// A top-down view of a map, made without the graphics context so that it
+/// can be done on another thread.
+#[derive(Derivative, Clone)]
+#[derivative(Debug)]
+pub struct ThumbnailPixels {
+    /// RGBA pixels, `size` by `size`
+    #[derivative(Debug = "ignore")]
+    pub rgba: Vec<u8>,
+    pub size: u16,
+    /// Size of the full map
+    pub width: u16,
+    pub height: u16,
+}
+
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct Map {
@@ -29,12 +60,7 @@ pub struct Map {
 
 impl Map {
     pub fn new(ctx: &mut Context, id: i32) -> GameResult<Self> {
-        let color_image_name = format!("/maps/C{}.png", id);
-        let height_image_name = format!("/maps/D{}.png", id);
-        let color_image = graphics::Image::new(ctx, color_image_name)?;
-        let height_image = graphics::Image::new(ctx, height_image_name)?;
-        assert_eq!(color_image.dimensions(), height_image.dimensions());
-
+        let (color_image, height_image) = load_images(ctx, id)?;
         let color_image_pixels = color_image.to_rgba8(ctx)?;
         let height_image_pixels = height_image.to_rgba8(ctx)?;
         let size = color_image.width() as i32;
@@ -53,6 +79,50 @@ impl Map {
         Ok(Map { id, colors, height_map, size, period: size - 1, shift, batch })
     }
 
+    /// The color and height images of a map as they are on disk, for
+    /// `thumbnail`.
+    pub fn read_images(ctx: &mut Context, id: i32) -> GameResult<(Vec<u8>, Vec<u8>)> {
+        let mut read = |path: String| -> GameResult<Vec<u8>> {
+            let mut bytes = Vec::new();
+            ggez::filesystem::open(ctx, path)?.read_to_end(&mut bytes)?;
+            Ok(bytes)
+        };
+        Ok((read(format!("/maps/C{}.png", id))?, read(format!("/maps/D{}.png", id))?))
+    }
+
+    /// A top-down view of a map, `size` pixels square and shaded by height,
+    /// from the images read by `read_images`. Doesn't touch the graphics
+    /// context, so it can be done off the main thread.
+    pub fn thumbnail(
+        color_png: &[u8],
+        height_png: &[u8],
+        size: u16,
+    ) -> GameResult<ThumbnailPixels> {
+        let (colors, color_samples, width, height) = decode_png(color_png)?;
+        let (heights, height_samples, height_width, height_height) = decode_png(height_png)?;
+        if (width, height) != (height_width, height_height) {
+            return Err(GameError::ResourceLoadError(
+                "map color and height images differ in size".to_owned(),
+            ));
+        }
+
+        let (columns, rows) = (width as usize, height as usize);
+        let mut rgba = Vec::with_capacity(size as usize * size as usize * 4);
+        for y in 0..size as usize {
+            for x in 0..size as usize {
+                let idx = (y * rows / size as usize) * columns + x * columns / size as usize;
+                let shade = THUMBNAIL_SHADE
+                    + (1.0 - THUMBNAIL_SHADE) * heights[idx * height_samples] as f32 / 255.0;
+                let color = &colors[idx * color_samples..(idx + 1) * color_samples];
+                // grayscale images have a single channel for all three
+                let rgb = (0..3).map(|channel| color[if color_samples < 3 { 0 } else { channel }]);
+                rgba.extend(rgb.map(|c| (c as f32 * shade) as u8));
+                rgba.push(255);
+            }
+        }
+        Ok(ThumbnailPixels { rgba, size, width, height })
+    }
+
     pub fn get_id(&self) -> i32 {
         self.id
     }
@@ -67,8 +137,28 @@ impl Map {
         (color, map_height)
     }
 
-    pub fn draw(&mut self, ctx: &mut Context, param: &MapDrawParam) -> GameResult {
+    /// Height of the terrain underneath the given world position.
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        let (_color, map_height) = self.get_point(x as i32, z as i32);
+        map_height as f32
+    }
+
+    /// Renders the map, optionally filling `depth` with the distance to
+    /// every pixel that was drawn.
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        param: &MapDrawParam,
+        mut depth: Option<&mut DepthBuffer>,
+    ) -> GameResult {
         let (width, height) = ggez::graphics::drawable_size(ctx);
+        if let Some(depth) = depth.as_deref_mut() {
+            depth.reset(width as usize, height as usize);
+        }
+        // sprites are placed relative to the screen center so roll can rotate around it
+        let center = Vec2::new(width / 2.0, height / 2.0);
+        let horizon = param.pitched_horizon();
+
         // visibility array
         let mut visibility = vec![height; width as usize];
         let mut z = 1.0;
@@ -91,30 +181,144 @@ impl Map {
                 // get color and height from map at the point
                 let (color, map_height) = self.get_point(map_x as i32, map_y as i32);
 
-                // perspective projection for height
-                // aka voodoo magic
-                let mut y = param.camera.y() - map_height as f32;
-                y = (y * invz + param.horizon).clamp(0.0, height);
+                let y = project(param, map_height, invz, horizon, height);
 
                 if y < *visible_y {
-                    self.draw_vertical_line(screen_x as f32, y, *visible_y, color);
+                    self.draw_vertical_line(screen_x as f32, y, *visible_y, center, color);
+                    if let Some(depth) = depth.as_deref_mut() {
+                        depth.fill_span(
+                            screen_x,
+                            y.round() as usize,
+                            visible_y.round() as usize,
+                            z,
+                        );
+                    }
                     *visible_y = y
                 }
             }
 
-            z += (z / RENDER_DETAIL).max(1.0);
+            z = next_distance(z, param.detail);
         }
 
-        graphics::draw(ctx, &self.batch, DrawParam::new())?;
+        let zoom = roll_zoom(param.roll, width, height);
+        graphics::draw(
+            ctx,
+            &self.batch,
+            DrawParam::new().dest(center).rotation(-param.roll).scale(Vec2::new(zoom, zoom)),
+        )?;
         self.batch.clear();
 
         Ok(())
     }
 
-    fn draw_vertical_line(&mut self, x: f32, y1: f32, y2: f32, color: Color) {
+    /// Finds the terrain point drawn at the given screen position, or `None`
+    /// if the ray from that pixel only hits sky.
+    ///
+    /// This undoes the camera roll and walks the same ray `draw` would for
+    /// the pixel's column, so the result matches what is on screen without
+    /// needing a depth buffer.
+    pub fn pick(
+        &self,
+        param: &MapDrawParam,
+        screen_size: (f32, f32),
+        screen_x: f32,
+        screen_y: f32,
+    ) -> Option<Pick> {
+        let (width, height) = screen_size;
+
+        // rotate the point back into the unrolled image
+        let (sin, cos) = param.roll.sin_cos();
+        let zoom = roll_zoom(param.roll, width, height);
+        let (dx, dy) = ((screen_x - width / 2.0) / zoom, (screen_y - height / 2.0) / zoom);
+        let screen_x = dx * cos - dy * sin + width / 2.0;
+        let screen_y = dx * sin + dy * cos + height / 2.0;
+
+        if screen_x < 0.0 || screen_x >= width || screen_y < 0.0 || screen_y >= height {
+            return None;
+        }
+
+        let left = param.rotation + param.fov / 2.0;
+        let right = param.rotation - param.fov / 2.0;
+        let phi = left + (right - left) / width * screen_x.floor();
+        let horizon = param.pitched_horizon();
+
+        let mut z = 1.0;
+        while z < param.view_distance {
+            let invz = 1.0 / z * param.height_scale;
+            let map_x = param.camera.x() + phi.cos() * z;
+            let map_y = param.camera.z() + phi.sin() * z;
+            let (_color, map_height) = self.get_point(map_x as i32, map_y as i32);
+
+            // the first span that reaches up to the pixel is the one drawn there
+            if project(param, map_height, invz, horizon, height) <= screen_y {
+                let point = Vec3::new(map_x, map_height as f32, map_y);
+                return Some(Pick { point, distance: z });
+            }
+
+            z = next_distance(z, param.detail);
+        }
+
+        None
+    }
+
+    fn draw_vertical_line(&mut self, x: f32, y1: f32, y2: f32, center: Vec2, color: Color) {
         assert!(y1 <= y2);
         self.batch.add(
-            DrawParam::new().dest(Vec2::new(x, y1)).color(color).scale(Vec2::new(1.0, y2 - y1)),
+            DrawParam::new()
+                .dest(Vec2::new(x, y1) - center)
+                .color(color)
+                .scale(Vec2::new(1.0, y2 - y1)),
         );
     }
 }
+
+/// Perspective projection of a terrain height to a screen row
+/// aka voodoo magic
+#[inline]
+fn project(
+    param: &MapDrawParam,
+    map_height: u8,
+    invz: f32,
+    horizon: f32,
+    screen_height: f32,
+) -> f32 {
+    let y = param.camera.y() - map_height as f32;
+    (y * invz + horizon).clamp(0.0, screen_height)
+}
+
+/// How much a rolled image has to be scaled up so that it still covers
+/// the whole screen.
+fn roll_zoom(roll: f32, width: f32, height: f32) -> f32 {
+    let aspect = width.max(height) / width.min(height);
+    roll.cos().abs() + roll.sin().abs() * aspect
+}
+
+/// Distance of the next ray sample after `z`. Samples get sparser further
+/// away, starting at a distance of `detail`.
+#[inline]
+fn next_distance(z: f32, detail: f32) -> f32 {
+    z + (z / detail).max(1.0)
+}
+
+/// Decodes a PNG into 8 bit samples, along with how many samples each pixel
+/// has and the width and height.
+fn decode_png(bytes: &[u8]) -> GameResult<(Vec<u8>, usize, u16, u16)> {
+    let decode_error = |err: png::DecodingError| {
+        GameError::ResourceLoadError(format!("failed to decode map image: {}", err))
+    };
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(decode_error)?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).map_err(decode_error)?;
+    pixels.truncate(info.buffer_size());
+    Ok((pixels, info.color_type.samples(), info.width as u16, info.height as u16))
+}
+
+/// The color and height images of a map.
+fn load_images(ctx: &mut Context, id: i32) -> GameResult<(Image, Image)> {
+    let color_image = graphics::Image::new(ctx, format!("/maps/C{}.png", id))?;
+    let height_image = graphics::Image::new(ctx, format!("/maps/D{}.png", id))?;
+    assert_eq!(color_image.dimensions(), height_image.dimensions());
+    Ok((color_image, height_image))
+}
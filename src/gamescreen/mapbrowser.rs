@@ -0,0 +1,335 @@
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use ggez::event::KeyCode;
+use ggez::event::MouseButton;
+use ggez::graphics;
+use ggez::graphics::Color;
+use ggez::graphics::DrawMode;
+use ggez::graphics::DrawParam;
+use ggez::graphics::Font;
+use ggez::graphics::Image;
+use ggez::graphics::Mesh;
+use ggez::graphics::Rect;
+use ggez::graphics::Text;
+use ggez::graphics::TextFragment;
+use ggez::Context;
+use ggez::GameError;
+use ggez::GameResult;
+use glam::*;
+
+use super::map::Map;
+use super::map::ThumbnailPixels;
+use super::MAP_COUNT;
+use crate::game::input::types::InputEffect;
+use crate::game::input::types::InputType;
+use crate::game::input::types::WheelDirection;
+use crate::game::input::Event;
+use crate::game::Scene;
+use crate::game::Transition;
+use crate::game::World;
+
+const BACKGROUND: Color = Color { r: 0.08, g: 0.1, b: 0.12, a: 1.0 };
+const TEXT: Color = Color { r: 0.85, g: 0.85, b: 0.85, a: 1.0 };
+const SELECTED: Color = Color { r: 1.0, g: 0.85, b: 0.3, a: 1.0 };
+const PLACEHOLDER: Color = Color { r: 0.15, g: 0.18, b: 0.2, a: 1.0 };
+const FONT_SIZE: f32 = 16.0;
+const INSTRUCTIONS: &str = "Arrows/Mouse: Select  Enter/Click: Load  Escape: Close";
+const THUMBNAIL_SIZE: u16 = 128;
+/// Space between the edge of the window and the grid
+const MARGIN: f32 = 20.0;
+/// Space above the grid for the title and instructions
+const HEADER: f32 = 70.0;
+/// Space under each thumbnail for its name and size
+const LABEL_HEIGHT: f32 = 40.0;
+/// Space between grid cells
+const SPACING: f32 = 16.0;
+
+/// The map picked in the browser, handed to the scene underneath as the
+/// browser closes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MapChoice(pub i32);
+
+/// A map's preview, and how big the map is.
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct Thumbnail {
+    #[derivative(Debug = "ignore")]
+    image: Image,
+    width: u16,
+    height: u16,
+}
+
+/// A map to make a thumbnail of, with its color and height images.
+type ThumbnailJob = (usize, Vec<u8>, Vec<u8>);
+type ThumbnailResult = (usize, Result<ThumbnailPixels, String>);
+
+/// Shows every map in a grid of thumbnails to pick one from. Decoding every
+/// map takes a while, so the thumbnails are made one at a time on another
+/// thread and fill in as the browser is open. The chosen map is handed back as a
+/// `MapChoice`, for the scene underneath to load once the browser pops off.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct MapBrowser {
+    #[derivative(Debug = "ignore")]
+    font: Font,
+    /// Each map's thumbnail once it's been made, or why it couldn't be
+    thumbnails: Vec<Option<Result<Thumbnail, String>>>,
+    /// Map the worker thread is making a thumbnail of
+    pending: Option<usize>,
+    #[derivative(Debug = "ignore")]
+    jobs: Sender<ThumbnailJob>,
+    #[derivative(Debug = "ignore")]
+    done: Receiver<ThumbnailResult>,
+    selected: usize,
+    /// Pixels the grid is scrolled up by to keep the selection on screen
+    scroll: f32,
+    choice: Option<MapChoice>,
+    closed: bool,
+}
+
+impl MapBrowser {
+    /// Opens the browser with `map_id` selected.
+    pub fn new(ctx: &mut Context, map_id: i32) -> GameResult<Self> {
+        let (jobs, worker_jobs) = mpsc::channel::<ThumbnailJob>();
+        let (worker_done, done) = mpsc::channel();
+        // stops once the browser is gone and can't send any more jobs
+        thread::spawn(move || {
+            for (index, color, height) in worker_jobs {
+                let pixels =
+                    Map::thumbnail(&color, &height, THUMBNAIL_SIZE).map_err(|err| err.to_string());
+                if worker_done.send((index, pixels)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(MapBrowser {
+            font: Font::new(ctx, "/LiberationMono-Regular.ttf")?,
+            thumbnails: (0..MAP_COUNT).map(|_| None).collect(),
+            pending: None,
+            jobs,
+            done,
+            selected: (map_id - 1).clamp(0, MAP_COUNT - 1) as usize,
+            scroll: 0.0,
+            choice: None,
+            closed: false,
+        })
+    }
+
+    /// How many thumbnails fit across the window.
+    fn columns(ctx: &Context) -> usize {
+        let (width, _height) = graphics::drawable_size(ctx);
+        let cell = THUMBNAIL_SIZE as f32 + SPACING;
+        (((width - 2.0 * MARGIN + SPACING) / cell) as usize).max(1)
+    }
+
+    /// Where the thumbnail of the map at `index` goes on screen.
+    fn cell_rect(&self, ctx: &Context, index: usize) -> Rect {
+        let columns = Self::columns(ctx);
+        let (column, row) = (index % columns, index / columns);
+        let size = THUMBNAIL_SIZE as f32;
+        Rect::new(
+            MARGIN + column as f32 * (size + SPACING),
+            HEADER + row as f32 * (size + LABEL_HEIGHT + SPACING) - self.scroll,
+            size,
+            size,
+        )
+    }
+
+    /// The map whose thumbnail is under a point on screen.
+    fn map_at(&self, ctx: &Context, point: Vec2) -> Option<usize> {
+        (0..self.thumbnails.len()).find(|&index| self.cell_rect(ctx, index).contains(point))
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index.min(self.thumbnails.len() - 1);
+    }
+
+    /// Fills in a map's cell. A map that can't be read gets a note in its
+    /// cell instead, and the rest of the maps carry on loading.
+    fn finish(&mut self, index: usize, thumbnail: Result<Thumbnail, String>) {
+        if let Err(err) = &thumbnail {
+            eprintln!("map {}: {}", index + 1, err);
+        }
+        self.thumbnails[index] = Some(thumbnail);
+        self.pending = None;
+    }
+
+    /// Hands the selected map to the scene underneath and closes.
+    fn choose(&mut self) {
+        self.choice = Some(MapChoice(self.selected as i32 + 1));
+        self.closed = true;
+    }
+
+    /// Scrolls the grid just enough that the selected thumbnail and its
+    /// label are on screen.
+    fn scroll_to_selected(&mut self, ctx: &Context) {
+        let (_width, height) = graphics::drawable_size(ctx);
+        let rect = self.cell_rect(ctx, self.selected);
+        if rect.y < HEADER {
+            self.scroll -= HEADER - rect.y;
+        } else if rect.y + rect.h + LABEL_HEIGHT > height {
+            self.scroll += rect.y + rect.h + LABEL_HEIGHT - height;
+        }
+    }
+
+    fn navigate(&mut self, ctx: &Context, key: KeyCode) {
+        let columns = Self::columns(ctx);
+        let count = self.thumbnails.len();
+        match key {
+            KeyCode::Left => self.select((self.selected + count - 1) % count),
+            KeyCode::Right => self.select((self.selected + 1) % count),
+            KeyCode::Up => self.select(self.selected.saturating_sub(columns)),
+            KeyCode::Down => self.select(self.selected + columns),
+            KeyCode::Home => self.select(0),
+            KeyCode::End => self.select(count - 1),
+            KeyCode::Return => self.choose(),
+            KeyCode::Escape => self.closed = true,
+            _ => (),
+        }
+    }
+}
+
+impl Scene for MapBrowser {
+    fn update(&mut self, _state: &mut World, ctx: &mut Context) -> GameResult<Transition> {
+        if self.closed {
+            return Ok(match self.choice {
+                Some(choice) => Transition::Return(Box::new(choice)),
+                None => Transition::Pop,
+            });
+        }
+
+        if let Ok((index, pixels)) = self.done.try_recv() {
+            let thumbnail = pixels.and_then(|pixels| {
+                let image = Image::from_rgba8(ctx, pixels.size, pixels.size, &pixels.rgba)
+                    .map_err(|err| err.to_string())?;
+                Ok(Thumbnail { image, width: pixels.width, height: pixels.height })
+            });
+            self.finish(index, thumbnail);
+        }
+        if self.pending.is_some() {
+            return Ok(Transition::None);
+        }
+
+        // the selected map first, since that's the one being looked at
+        let missing = if self.thumbnails[self.selected].is_none() {
+            Some(self.selected)
+        } else {
+            self.thumbnails.iter().position(Option::is_none)
+        };
+        if let Some(index) = missing {
+            match Map::read_images(ctx, index as i32 + 1) {
+                Ok((color, height)) => {
+                    self.jobs.send((index, color, height)).map_err(|_| {
+                        GameError::ResourceLoadError("thumbnail thread stopped".to_owned())
+                    })?;
+                    self.pending = Some(index);
+                }
+                Err(err) => self.finish(index, Err(err.to_string())),
+            }
+        }
+        Ok(Transition::None)
+    }
+
+    fn draw(&mut self, _state: &mut World, ctx: &mut Context) -> GameResult {
+        graphics::clear(ctx, BACKGROUND);
+        self.scroll_to_selected(ctx);
+
+        let fragment = |text: String, color: Color| {
+            TextFragment::new(text).color(color).font(self.font).scale(FONT_SIZE)
+        };
+
+        let title = Text::new(fragment(format!("Maps\n{}", INSTRUCTIONS), TEXT));
+        graphics::draw(ctx, &title, (Vec2::new(MARGIN, MARGIN),))?;
+
+        let (_width, height) = graphics::drawable_size(ctx);
+        for (index, thumbnail) in self.thumbnails.iter().enumerate() {
+            let rect = self.cell_rect(ctx, index);
+            if rect.y + rect.h + LABEL_HEIGHT < HEADER || rect.y > height {
+                continue;
+            }
+
+            let size = match thumbnail {
+                Some(Ok(thumbnail)) => {
+                    graphics::draw(ctx, &thumbnail.image, DrawParam::new().dest([rect.x, rect.y]))?;
+                    format!("{}x{}", thumbnail.width, thumbnail.height)
+                }
+                _ => {
+                    let placeholder =
+                        Mesh::new_rectangle(ctx, DrawMode::fill(), rect, PLACEHOLDER)?;
+                    graphics::draw(ctx, &placeholder, DrawParam::new())?;
+                    let status = if thumbnail.is_some() { "Unreadable" } else { "Loading..." };
+                    status.to_owned()
+                }
+            };
+
+            let color = if index == self.selected { SELECTED } else { TEXT };
+            if index == self.selected {
+                let outline = Mesh::new_rectangle(ctx, DrawMode::stroke(3.0), rect, SELECTED)?;
+                graphics::draw(ctx, &outline, DrawParam::new())?;
+            }
+            let label = Text::new(fragment(format!("Map {}\n{}", index + 1, size), color));
+            graphics::draw(ctx, &label, (Vec2::new(rect.x, rect.y + rect.h + 4.0),))?;
+        }
+        Ok(())
+    }
+
+    /// Hovering over a thumbnail selects it.
+    fn input(
+        &mut self,
+        _state: &mut World,
+        ctx: &mut Context,
+        event: Event,
+        _started: bool,
+    ) -> GameResult<Transition> {
+        if let InputEffect::Pointer(x, y, _dx, _dy) = event {
+            if let Some(index) = self.map_at(ctx, Vec2::new(x, y)) {
+                self.select(index);
+            }
+        }
+        Ok(Transition::None)
+    }
+
+    /// Takes every key, button and wheel turn, so the map's controls don't
+    /// fire underneath.
+    fn raw_input(
+        &mut self,
+        state: &mut World,
+        ctx: &mut Context,
+        input: InputType,
+        started: bool,
+    ) -> GameResult<bool> {
+        if !started {
+            return Ok(true);
+        }
+
+        let columns = Self::columns(ctx);
+        match input {
+            InputType::KeyEvent(key) => self.navigate(ctx, key),
+            InputType::MouseButtonEvent(MouseButton::Left) => {
+                let (x, y) = state.input.mouse_position();
+                if let Some(index) = self.map_at(ctx, Vec2::new(x, y)) {
+                    self.select(index);
+                    self.choose();
+                }
+            }
+            InputType::MouseWheelEvent(WheelDirection::Up) => {
+                self.select(self.selected.saturating_sub(columns))
+            }
+            InputType::MouseWheelEvent(WheelDirection::Down) => {
+                self.select(self.selected + columns)
+            }
+            _ => (),
+        }
+        Ok(true)
+    }
+
+    /// Keys held when the browser closes were let go of while it was open.
+    fn on_exit(&mut self, state: &mut World, _ctx: &mut Context) -> GameResult {
+        state.input.reset_input_state();
+        Ok(())
+    }
+}
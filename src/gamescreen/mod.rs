@@ -1,4 +1,14 @@
+use std::any::Any;
 use std::f32::consts::PI;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 use game::input::Axis;
 use game::input::Button;
@@ -7,18 +17,62 @@ use ggez::input::mouse::CursorIcon;
 use ggez::{self};
 use glam::*;
 
+mod bench;
+mod bookmarks;
+mod camerapath;
+mod capture;
+mod commands;
+mod console;
+mod controlsmenu;
 mod debugtext;
+mod depth;
+mod helicopter;
 mod map;
+mod mapbrowser;
+mod pausemenu;
+mod screenshot;
+mod settings;
+mod walk;
 
+pub use bench::BenchConfig;
+pub use bench::BenchState;
+pub use bench::BENCH_USAGE;
+use bookmarks::Bookmark;
+use bookmarks::Bookmarks;
+use camerapath::CameraPath;
+use camerapath::Easing;
+use camerapath::Interpolation;
+use camerapath::PathPlayback;
+use camerapath::PathRecorder;
+use camerapath::PathStyle;
+use camerapath::Timing;
+use capture::FrameCapture;
+pub use capture::RecordConfig;
+pub use capture::RECORD_USAGE;
+use controlsmenu::ControlsMenu;
 use debugtext::DebugText;
+use depth::DepthBuffer;
 use game::input::types::InputEffect;
 use game::Scene;
+use game::Transition;
 use game::World;
-use ggez::event;
 use ggez::graphics;
 use ggez::Context;
+use ggez::GameError;
 use ggez::GameResult;
+use helicopter::Helicopter;
 use map::Map;
+use map::Pick;
+use mapbrowser::MapBrowser;
+use mapbrowser::MapChoice;
+use pausemenu::PauseMenu;
+use pausemenu::Tuning;
+use screenshot::SavedView;
+pub use settings::PlayConfig;
+pub use settings::Settings;
+pub use settings::SettingsStore;
+pub use settings::PLAY_USAGE;
+use walk::Walker;
 
 use crate::game;
 
@@ -38,6 +92,21 @@ const DEFAULT_H_SPEED: f32 = 75.50;
 const DEFAULT_HS_SENS: f32 = 20.0;
 const DEFAULT_FOV_SPEED: f32 = 40.0 * TO_RADIANS;
 const DEFAULT_V_SPEED: f32 = 150.0;
+const DEFAULT_TURN_SPEED: f32 = 90.0 * TO_RADIANS;
+const DEFAULT_LOOK_SPEED: f32 = 300.0;
+const DEFAULT_MOUSE_SENSITIVITY: f32 = 0.15 * TO_RADIANS;
+const DEFAULT_RENDER_DETAIL: f32 = 150.0;
+const TELEPORT_CLEARANCE: f32 = 20.0;
+const DEFAULT_CLEARANCE: f32 = 5.0;
+const CAMERA_PATH_FILE: &str = "camera_path.txt";
+const BOOKMARKS_FILE: &str = "bookmarks.txt";
+/// Seconds a notice stays on screen
+const NOTICE_TIME: f32 = 2.0;
+const DEFAULT_PATH_STYLE: PathStyle = PathStyle {
+    interpolation: Interpolation::CatmullRom,
+    easing: Easing::EaseInOut,
+    timing: Timing::Speed(DEFAULT_SPEED),
+};
 
 // First we make a structure to contain the game's state
 #[derive(Debug)]
@@ -45,16 +114,96 @@ pub struct MapState {
     map_id: i32,
     pub map: Map,
     pub draw_param: MapDrawParam,
+    /// The view before the last update, for interpolating between updates
+    previous_param: MapDrawParam,
+    /// Draw frames between updates instead of repeating the last update
+    pub interpolate: bool,
     pub draw_debug: bool,
     pub debug: DebugText,
+    pub depth: DepthBuffer,
     pub speed: f32,
     pub h_speed: f32,
     pub hs_sens: f32,
     pub fov_speed: f32,
     pub v_speed: f32,
+    /// Radians per second at full `Axis::Turn`
+    pub turn_speed: f32,
+    /// Horizon pixels per second at full `Axis::Look`
+    pub look_speed: f32,
+    /// Radians the camera turns per unit of mouse motion in mouse look
+    pub mouse_sensitivity: f32,
+    /// Look down when the mouse moves up in mouse look
+    pub invert_mouse: bool,
+    /// Whether the cursor is captured and moving the mouse turns the camera
+    mouse_look: bool,
+    /// The cursor was just put back in the middle of the window, so the
+    /// next motion event comes from that rather than the player. Its
+    /// position can't tell, since the window may not put the cursor
+    /// exactly where it was asked to
+    recentered: bool,
+    /// Minimum height the camera is kept above the terrain
+    pub clearance: f32,
+    /// Altitude above the terrain to hold the camera at, if ground follow is on
+    pub ground_follow: Option<f32>,
+    pub movement: MovementMode,
+    pub walker: Walker,
+    pub helicopter: Helicopter,
+    /// How recorded camera paths are played back
+    pub path_style: PathStyle,
+    recorder: Option<PathRecorder>,
+    playback: Option<PathPlayback>,
+    capture: Option<FrameCapture>,
+    /// Another scene is on top, so the map may be drawn but isn't updated
+    paused: bool,
+    /// Quit once path playback is done, for rendering videos
+    quit_after_playback: bool,
+    /// Save a screenshot once the current frame is drawn
+    screenshot_requested: bool,
+    /// Fill in and save the depth buffer while drawing the current frame
+    depth_requested: bool,
+    /// Where settings changes are saved, if they are
+    settings: Option<SettingsStore>,
+    /// Saved views on every map
+    bookmarks: Bookmarks,
+    /// Which of the current map's bookmarks was last jumped to
+    bookmark: Option<usize>,
+    /// Message shown at the bottom of the screen, and the seconds until it
+    /// goes away
+    notice: Option<(String, f32)>,
 }
 
-#[derive(Derivative)]
+/// How the controls move the camera around.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MovementMode {
+    /// Free flight, optionally following the terrain
+    Fly,
+    /// First person walking with gravity
+    Walk,
+    /// Flight with momentum, banking and pitching
+    Helicopter,
+}
+
+impl MovementMode {
+    pub fn next(self) -> Self {
+        match self {
+            MovementMode::Fly => MovementMode::Walk,
+            MovementMode::Walk => MovementMode::Helicopter,
+            MovementMode::Helicopter => MovementMode::Fly,
+        }
+    }
+}
+
+impl fmt::Display for MovementMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovementMode::Fly => write!(f, "Fly"),
+            MovementMode::Walk => write!(f, "Walk"),
+            MovementMode::Helicopter => write!(f, "Helicopter"),
+        }
+    }
+}
+
+#[derive(Derivative, Clone)]
 #[derivative(Debug)]
 pub struct MapDrawParam {
     #[derivative(Debug(format_with = "vec3_fmt"))]
@@ -64,6 +213,62 @@ pub struct MapDrawParam {
     pub view_distance: f32,
     pub horizon: f32,
     pub fov: f32,
+    /// Camera roll in radians, positive banks to the right
+    pub roll: f32,
+    /// Camera pitch in radians on top of `horizon`, positive looks up
+    pub pitch: f32,
+    /// How far from the camera the terrain starts being sampled more
+    /// sparsely, higher is sharper and slower
+    pub detail: f32,
+}
+
+impl MapDrawParam {
+    /// Turns the view by `rotation` radians and moves the horizon by
+    /// `horizon` pixels.
+    pub fn turn(&mut self, rotation: f32, horizon: f32) {
+        let full = 2.0 * PI;
+        self.rotation = ((self.rotation + rotation) % full + full) % full;
+        self.horizon += horizon;
+    }
+
+    /// The view `t` of the way from `previous` to this one.
+    pub fn interpolate(&self, previous: &MapDrawParam, t: f32) -> MapDrawParam {
+        let lerp = |from: f32, to: f32| from + (to - from) * t;
+        // turn the short way around
+        let full = 2.0 * PI;
+        let turn = ((self.rotation - previous.rotation) % full + full + PI) % full - PI;
+        MapDrawParam {
+            camera: previous.camera + (self.camera - previous.camera) * t,
+            rotation: ((previous.rotation + turn * t) % full + full) % full,
+            height_scale: lerp(previous.height_scale, self.height_scale),
+            view_distance: lerp(previous.view_distance, self.view_distance),
+            horizon: lerp(previous.horizon, self.horizon),
+            fov: lerp(previous.fov, self.fov),
+            roll: lerp(previous.roll, self.roll),
+            pitch: lerp(previous.pitch, self.pitch),
+            detail: self.detail,
+        }
+    }
+
+    /// Every plain number, by the name it's saved and set under.
+    pub fn scalar_fields(&mut self) -> Vec<(&'static str, &mut f32)> {
+        vec![
+            ("rotation", &mut self.rotation),
+            ("height_scale", &mut self.height_scale),
+            ("view_distance", &mut self.view_distance),
+            ("horizon", &mut self.horizon),
+            ("fov", &mut self.fov),
+            ("roll", &mut self.roll),
+            ("pitch", &mut self.pitch),
+            ("detail", &mut self.detail),
+        ]
+    }
+
+    /// Screen row of the horizon once `pitch` is taken into account.
+    pub fn pitched_horizon(&self) -> f32 {
+        // height_scale doubles as the focal length of the projection
+        self.horizon + self.height_scale * self.pitch.tan()
+    }
 }
 
 impl Default for MapDrawParam {
@@ -75,6 +280,9 @@ impl Default for MapDrawParam {
             view_distance: DEFAULT_VIEW_DISTANCE,
             horizon: DEFAULT_HORIZON,
             fov: DEFAULT_FOV,
+            roll: 0.0,
+            pitch: 0.0,
+            detail: DEFAULT_RENDER_DETAIL,
         }
     }
 }
@@ -86,74 +294,577 @@ impl MapState {
             map: Map::new(ctx, map_id)?,
             draw_debug: true,
             debug: DebugText::new(ctx)?,
+            depth: DepthBuffer::new(),
             draw_param: MapDrawParam::default(),
+            previous_param: MapDrawParam::default(),
+            interpolate: true,
             speed: DEFAULT_SPEED,
             h_speed: DEFAULT_H_SPEED,
             hs_sens: DEFAULT_HS_SENS,
             fov_speed: DEFAULT_FOV_SPEED,
             v_speed: DEFAULT_V_SPEED,
+            turn_speed: DEFAULT_TURN_SPEED,
+            look_speed: DEFAULT_LOOK_SPEED,
+            mouse_sensitivity: DEFAULT_MOUSE_SENSITIVITY,
+            invert_mouse: false,
+            mouse_look: false,
+            recentered: false,
+            clearance: DEFAULT_CLEARANCE,
+            ground_follow: None,
+            movement: MovementMode::Fly,
+            walker: Walker::new(),
+            helicopter: Helicopter::new(),
+            path_style: DEFAULT_PATH_STYLE,
+            recorder: None,
+            playback: None,
+            capture: None,
+            paused: false,
+            quit_after_playback: false,
+            screenshot_requested: false,
+            depth_requested: false,
+            settings: None,
+            bookmarks: Self::load_bookmarks(ctx),
+            bookmark: None,
+            notice: None,
         })
     }
 
+    /// Starts the game with the given settings and keeps them saved as they
+    /// change.
+    pub fn use_settings(&mut self, ctx: &mut Context, store: SettingsStore) -> GameResult {
+        let settings = store.settings().clone();
+        self.apply_settings(ctx, &settings)?;
+        self.settings = Some(store);
+        Ok(())
+    }
+
+    /// Takes on the settings that belong to the map, leaving the window and
+    /// input settings to the caller.
+    fn apply_settings(&mut self, ctx: &mut Context, settings: &Settings) -> GameResult {
+        if settings.map_id != self.map_id {
+            self.map_id = settings.map_id;
+            self.map = Map::new(ctx, self.map_id)?;
+        }
+        self.draw_param.fov = settings.fov;
+        self.speed = settings.speed;
+        self.h_speed = settings.h_speed;
+        self.v_speed = settings.v_speed;
+        self.fov_speed = settings.fov_speed;
+        self.hs_sens = settings.hs_sens;
+        self.mouse_sensitivity = settings.mouse_sensitivity;
+        self.invert_mouse = settings.invert_mouse;
+        self.interpolate = settings.interpolate;
+        self.draw_debug = settings.draw_debug;
+        Ok(())
+    }
+
+    /// The settings as they are right now.
+    fn current_settings(&self, ctx: &Context, world: &World) -> Settings {
+        let (width, height) = graphics::drawable_size(ctx);
+        Settings {
+            map_id: self.map_id,
+            fov: self.draw_param.fov,
+            speed: self.speed,
+            h_speed: self.h_speed,
+            v_speed: self.v_speed,
+            fov_speed: self.fov_speed,
+            hs_sens: self.hs_sens,
+            mouse_sensitivity: self.mouse_sensitivity,
+            invert_mouse: self.invert_mouse,
+            tick_rate: world.tick_rate,
+            interpolate: self.interpolate,
+            width,
+            height,
+            draw_debug: self.draw_debug,
+            analog: world.input.analog,
+        }
+    }
+
     fn reset(&mut self) {
-        // save fov
-        self.draw_param = MapDrawParam { fov: self.draw_param.fov, ..Default::default() }
+        // save fov and detail
+        self.draw_param = MapDrawParam {
+            fov: self.draw_param.fov,
+            detail: self.draw_param.detail,
+            ..Default::default()
+        };
+        self.stop();
     }
 
-    fn pointer_moved(
-        &mut self,
-        ctx: &mut Context,
-        state: &mut World,
-        _x: f32,
-        _y: f32,
-        dx: f32,
-        dy: f32,
-    ) {
-        if state.input.get_button_down(Button::Grab) {
-            let (_width, height) = ggez::graphics::drawable_size(ctx);
-            self.draw_param.rotation += (dx / height) * self.draw_param.fov;
+    /// The settings the pause menu can change, as they are right now.
+    fn tuning(&self) -> Tuning {
+        Tuning {
+            fov: self.draw_param.fov,
+            view_distance: self.draw_param.view_distance,
+            height_scale: self.draw_param.height_scale,
+            speed: self.speed,
+            mouse_sensitivity: self.mouse_sensitivity,
+            detail: self.draw_param.detail,
+        }
+    }
+
+    fn apply_tuning(&mut self, tuning: Tuning) {
+        // both views, so the change isn't interpolated
+        for param in &mut [&mut self.draw_param, &mut self.previous_param] {
+            param.fov = tuning.fov;
+            param.view_distance = tuning.view_distance;
+            param.height_scale = tuning.height_scale;
+            param.detail = tuning.detail;
+        }
+        self.speed = tuning.speed;
+        self.mouse_sensitivity = tuning.mouse_sensitivity;
+    }
 
-            let full = 2.0 * PI;
-            self.draw_param.rotation = (self.draw_param.rotation % full + full) % full;
+    /// Drops any momentum the movement modes have built up.
+    fn stop(&mut self) {
+        self.walker.stop();
+        self.helicopter.stop();
+        self.draw_param.roll = 0.0;
+        self.draw_param.pitch = 0.0;
+        // a jump to a new view shouldn't be smoothed over
+        self.previous_param = self.draw_param.clone();
+    }
 
-            self.draw_param.horizon += dy;
+    /// Captures and hides the cursor so the mouse turns the camera, or lets
+    /// it go again.
+    fn set_mouse_look(&mut self, ctx: &mut Context, on: bool) -> GameResult {
+        if on == self.mouse_look {
+            return Ok(());
+        }
+        self.mouse_look = on;
+        ggez::input::mouse::set_cursor_grabbed(ctx, on)?;
+        ggez::input::mouse::set_cursor_hidden(ctx, on);
+        if on {
+            self.recenter_cursor(ctx)?;
         }
+        Ok(())
+    }
+
+    /// Puts the cursor in the middle of the window, so it doesn't get stuck
+    /// against an edge in mouse look.
+    fn recenter_cursor(&mut self, ctx: &mut Context) -> GameResult {
+        let (width, height) = graphics::drawable_size(ctx);
+        ggez::input::mouse::set_position(ctx, Vec2::new(width / 2.0, height / 2.0))?;
+        self.recentered = true;
+        Ok(())
+    }
+
+    fn pointer_moved(&mut self, ctx: &mut Context, state: &mut World, dx: f32, dy: f32) {
+        let (_, height) = ggez::graphics::drawable_size(ctx);
+        let (rotation, horizon) = if self.mouse_look {
+            if self.recentered {
+                self.recentered = false;
+                return;
+            }
+
+            // same pixels per radian as dragging the view
+            let look = dy * self.mouse_sensitivity * height / self.draw_param.fov;
+            (-dx * self.mouse_sensitivity, if self.invert_mouse { look } else { -look })
+        } else if state.input.get_button_down(Button::Grab) {
+            ((dx / height) * self.draw_param.fov, dy)
+        } else {
+            return;
+        };
+
+        // the mouse moves the view between updates, so the last update's
+        // view moves along to keep it from being interpolated away
+        self.draw_param.turn(rotation, horizon);
+        self.previous_param.turn(rotation, horizon);
     }
 
     fn handle_button(
         &mut self,
         ctx: &mut Context,
-        _world: &mut World,
+        world: &mut World,
         button: Button,
         started: bool,
-    ) -> GameResult {
+    ) -> GameResult<Transition> {
         use Button::*;
         match (button, started) {
-            (Quit, true) => event::quit(ctx),
+            (Quit, true) => return Ok(Transition::Quit),
+            (Pause, true) => {
+                self.set_mouse_look(ctx, false)?;
+                world.input.reset_input_state();
+                let menu = PauseMenu::new(ctx, self.tuning())?;
+                return Ok(Transition::Push(Box::new(menu)));
+            }
             (Reload, true) => self.update_map(ctx, 0)?,
             (Next, true) => self.update_map(ctx, 1)?,
             (Prev, true) => self.update_map(ctx, -1)?,
+            (JumpNext, true) => self.update_map(ctx, 10)?,
+            (JumpPrev, true) => self.update_map(ctx, -10)?,
             (ToggleDebug, true) => self.draw_debug = !self.draw_debug,
+            (SaveDepth, true) => self.depth_requested = true,
+            (Teleport, true) => self.teleport_to_cursor(ctx, world),
+            (GroundFollow, true) => self.toggle_ground_follow(),
+            (Movement, true) => {
+                self.movement = self.movement.next();
+                self.stop();
+            }
+            (RecordPath, true) => self.toggle_recording(ctx)?,
+            (PlayPath, true) => self.toggle_playback(ctx)?,
+            (Capture, true) => self.toggle_capture(ctx, world)?,
+            (Screenshot, true) => self.screenshot_requested = true,
+            (MouseLook, true) => self.set_mouse_look(ctx, !self.mouse_look)?,
+            (Controls, true) => {
+                self.set_mouse_look(ctx, false)?;
+                world.input.reset_input_state();
+                return Ok(Transition::Push(Box::new(ControlsMenu::new(ctx)?)));
+            }
+            (SaveBookmark, true) => {
+                let name = self.bookmarks.unused_name(self.map_id);
+                self.save_bookmark(ctx, &name)?;
+                self.notify(world, &format!("Saved bookmark {}", name));
+            }
+            (NextBookmark, true) => self.cycle_bookmark(ctx, world, 1)?,
+            (PrevBookmark, true) => self.cycle_bookmark(ctx, world, -1)?,
+            (Console, true) => {
+                self.set_mouse_look(ctx, false)?;
+                world.input.reset_input_state();
+                return Ok(Transition::Push(Box::new(console::Console::new(ctx)?)));
+            }
+            (Maps, true) => {
+                self.set_mouse_look(ctx, false)?;
+                world.input.reset_input_state();
+                let browser = MapBrowser::new(ctx, self.map_id)?;
+                return Ok(Transition::Push(Box::new(browser)));
+            }
             (Grab, started) => {
                 let cursor = if started { CursorIcon::Grabbing } else { CursorIcon::Default };
                 ggez::input::mouse::set_cursor_type(ctx, cursor);
             }
             _ => (),
         }
+        Ok(Transition::None)
+    }
+
+    /// Finds the terrain point underneath the mouse cursor, or the middle
+    /// of the screen in mouse look.
+    pub fn pick_cursor(&self, ctx: &Context, world: &World) -> Option<Pick> {
+        let (width, height) = graphics::drawable_size(ctx);
+        let cursor = if self.mouse_look {
+            Vec2::new(width / 2.0, height / 2.0)
+        } else {
+            let (x, y) = world.input.mouse_position();
+            Vec2::new(x, y)
+        };
+        self.map.pick(&self.draw_param, (width, height), cursor.x(), cursor.y())
+    }
+
+    /// Moves the camera over the terrain point under the cursor, lifting it
+    /// if it would end up underground.
+    fn teleport_to_cursor(&mut self, ctx: &mut Context, world: &World) {
+        if let Some(pick) = self.pick_cursor(ctx, world) {
+            let camera = &mut self.draw_param.camera;
+            camera.set_x(pick.point.x());
+            camera.set_z(pick.point.z());
+            camera.set_y(camera.y().max(pick.point.y() + TELEPORT_CLEARANCE));
+            self.stop();
+        }
+    }
+
+    /// Height of the terrain directly underneath the camera.
+    fn ground_height(&self) -> f32 {
+        self.map.height_at(self.draw_param.camera.x(), self.draw_param.camera.z())
+    }
+
+    /// Starts holding the camera at its current altitude above the terrain,
+    /// or goes back to free flight.
+    fn toggle_ground_follow(&mut self) {
+        self.ground_follow = match self.ground_follow {
+            Some(_) => None,
+            None => Some(self.draw_param.camera.y() - self.ground_height()),
+        };
+    }
+
+    /// Free flight, kept above the terrain.
+    fn fly(&mut self, state: &World, walk: Vec3, dt: f32) {
+        self.draw_param.camera += walk * self.speed * dt;
+
+        // keep the camera above the terrain, optionally following it
+        let height_change = state.input.get_axis(Axis::Height) * self.h_speed * dt;
+        let ground = self.ground_height();
+        let camera_y = match &mut self.ground_follow {
+            Some(altitude) => {
+                *altitude = (*altitude + height_change).max(self.clearance);
+                ground + *altitude
+            }
+            None => self.draw_param.camera.y() + height_change,
+        };
+        self.draw_param.camera.set_y(camera_y.max(ground + self.clearance));
+    }
+
+    fn camera_path_file(ctx: &Context) -> PathBuf {
+        ggez::filesystem::user_data_dir(ctx).join(CAMERA_PATH_FILE)
+    }
+
+    /// Starts recording the camera, or stops and saves the recording.
+    fn toggle_recording(&mut self, ctx: &mut Context) -> GameResult {
+        match self.recorder.take() {
+            Some(recorder) => {
+                let path = recorder.finish(&self.draw_param);
+                fs::create_dir_all(ggez::filesystem::user_data_dir(ctx))?;
+                path.save(File::create(Self::camera_path_file(ctx))?)?;
+            }
+            None => {
+                self.playback = None;
+                self.recorder = Some(PathRecorder::new());
+            }
+        }
+        Ok(())
+    }
+
+    /// Plays back the last saved camera path, or stops playback.
+    fn toggle_playback(&mut self, ctx: &mut Context) -> GameResult {
+        if self.playback.take().is_some() {
+            return Ok(());
+        }
+
+        let file = Self::camera_path_file(ctx);
+        if self.recorder.is_none() && file.exists() {
+            let path = CameraPath::load(BufReader::new(File::open(file)?))?;
+            self.stop();
+            self.playback = PathPlayback::new(&path, self.path_style);
+        }
+        Ok(())
+    }
+
+    fn bookmarks_file(ctx: &Context) -> PathBuf {
+        ggez::filesystem::user_data_dir(ctx).join(BOOKMARKS_FILE)
+    }
+
+    /// Reads the saved bookmarks. A broken file is reported and moved aside
+    /// rather than stopping the game, so the next save doesn't lose it.
+    fn load_bookmarks(ctx: &Context) -> Bookmarks {
+        let file = Self::bookmarks_file(ctx);
+        if !file.exists() {
+            return Bookmarks::default();
+        }
+        File::open(&file)
+            .map_err(GameError::from)
+            .and_then(|reader| Bookmarks::load(BufReader::new(reader)))
+            .unwrap_or_else(|err| {
+                eprintln!("{}: {}, starting without bookmarks", file.display(), err);
+                back_up_broken_file(&file);
+                Bookmarks::default()
+            })
+    }
+
+    /// Bookmarks the current view under `name` and saves the bookmarks.
+    fn save_bookmark(&mut self, ctx: &mut Context, name: &str) -> GameResult {
+        self.bookmarks.insert(Bookmark::new(name, self.map_id, &self.draw_param));
+        fs::create_dir_all(ggez::filesystem::user_data_dir(ctx))?;
+        self.bookmarks.save(BufWriter::new(File::create(Self::bookmarks_file(ctx))?))
+    }
+
+    /// Removes a bookmark from the current map and saves the bookmarks.
+    fn delete_bookmark(&mut self, ctx: &mut Context, name: &str) -> GameResult<bool> {
+        if !self.bookmarks.remove(self.map_id, name) {
+            return Ok(false);
+        }
+        self.bookmark = None;
+        fs::create_dir_all(ggez::filesystem::user_data_dir(ctx))?;
+        self.bookmarks.save(BufWriter::new(File::create(Self::bookmarks_file(ctx))?))?;
+        Ok(true)
+    }
+
+    /// Jumps to a bookmarked view, switching maps if it's on another one.
+    fn go_to_bookmark(&mut self, ctx: &mut Context, bookmark: &Bookmark) -> GameResult {
+        if bookmark.map_id != self.map_id {
+            self.load_map(ctx, bookmark.map_id)?;
+        }
+        self.bookmark =
+            self.bookmarks.on_map(self.map_id).iter().position(|other| other.name == bookmark.name);
+        self.playback = None;
+        self.ground_follow = None;
+        bookmark.apply(&mut self.draw_param);
+        self.stop();
+        Ok(())
+    }
+
+    /// Jumps `change` bookmarks along the current map's bookmarks.
+    fn cycle_bookmark(&mut self, ctx: &mut Context, world: &mut World, change: i32) -> GameResult {
+        let bookmarks = self.bookmarks.on_map(self.map_id);
+        let count = bookmarks.len() as i32;
+        if count == 0 {
+            self.notify(world, &format!("No bookmarks on map {}", self.map_id));
+            return Ok(());
+        }
+
+        let index = match self.bookmark {
+            Some(index) => ((index as i32 + change) % count + count) % count,
+            None if change > 0 => 0,
+            None => count - 1,
+        };
+        let bookmark = bookmarks[index as usize].clone();
+        self.go_to_bookmark(ctx, &bookmark)?;
+        self.notify(world, &format!("Bookmark {}", bookmark.name));
+        Ok(())
+    }
+
+    /// Shows a message on screen for a moment, and in the console.
+    fn notify(&mut self, world: &mut World, text: &str) {
+        world.commands.print(text);
+        self.notice = Some((text.to_owned(), NOTICE_TIME));
+    }
+
+    /// Starts capturing every frame to a new PNG sequence in the user data
+    /// directory, or stops capturing.
+    fn toggle_capture(&mut self, ctx: &mut Context, world: &mut World) -> GameResult {
+        match self.capture.take() {
+            Some(capture) => {
+                world.frame_time = None;
+                capture.finish()
+            }
+            None => {
+                let timestamp =
+                    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+                let dir =
+                    ggez::filesystem::user_data_dir(ctx).join(format!("capture-{}", timestamp));
+                self.start_capture(
+                    world,
+                    FrameCapture::create(&dir, capture::DEFAULT_CAPTURE_FPS)?,
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Captures advance by a fixed step, so slow frames don't stutter in the
+    /// video.
+    fn start_capture(&mut self, world: &mut World, capture: FrameCapture) {
+        world.frame_time = Some(capture.frame_time());
+        self.capture = Some(capture);
+    }
+
+    /// Flies a camera path once while capturing every frame, then quits.
+    pub fn record_video(
+        &mut self,
+        ctx: &mut Context,
+        world: &mut World,
+        config: &RecordConfig,
+    ) -> GameResult {
+        let file = match &config.path {
+            Some(file) => PathBuf::from(file),
+            None => Self::camera_path_file(ctx),
+        };
+        let path = CameraPath::load(BufReader::new(File::open(file)?))?;
+        self.playback = PathPlayback::new(&path, self.path_style);
+        if self.playback.is_none() {
+            return Err(GameError::ConfigError("camera path is empty".to_owned()));
+        }
+
+        self.start_capture(world, FrameCapture::create(&config.out, config.fps)?);
+        self.quit_after_playback = true;
+        self.draw_debug = false;
+        Ok(())
+    }
+
+    /// What the camera is currently doing, for the debug text.
+    fn mode_text(&self) -> String {
+        let mode = if let Some(playback) = &self.playback {
+            format!("Playback {:.1}/{:.1}s", playback.elapsed(), playback.duration())
+        } else if self.recorder.is_some() {
+            format!("{} (Recording)", self.movement)
+        } else {
+            self.movement.to_string()
+        };
+
+        match &self.capture {
+            Some(capture) => {
+                format!("{} (Capturing {} frames to {:?})", mode, capture.frames(), capture.out())
+            }
+            None => mode,
+        }
+    }
+
+    /// Writes the depth buffer of the frame just drawn to the user data
+    /// directory.
+    fn save_depth(&self, ctx: &mut Context, view_distance: f32) -> GameResult {
+        let dir = ggez::filesystem::user_data_dir(ctx);
+        fs::create_dir_all(dir)?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let path = dir.join(format!("depth-{}-{}.png", self.map_id, timestamp));
+        self.depth.write_png(File::create(path)?, view_distance)
+    }
+
+    /// Saves what's on screen to the user data directory, with the view
+    /// embedded so it can be restored later.
+    fn save_screenshot(&self, ctx: &mut Context) -> GameResult {
+        let image = graphics::screenshot(ctx)?;
+        let (width, height) = (image.width() as u32, image.height() as u32);
+        let rgba = image.to_rgba8(ctx)?;
+
+        let dir = ggez::filesystem::user_data_dir(ctx);
+        fs::create_dir_all(dir)?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let path = dir.join(format!("screenshot-{}-{}.png", self.map_id, timestamp));
+        let view = SavedView { map_id: self.map_id, param: self.draw_param.clone() };
+        view.write_png(BufWriter::new(File::create(path)?), width, height, &rgba)
+    }
+
+    /// Jumps to the map and camera a screenshot was taken with. Only done
+    /// at startup, since ggez's `EventHandler` has no event for files
+    /// dropped onto the window.
+    pub fn restore_screenshot(&mut self, ctx: &mut Context, file: &Path) -> GameResult {
+        let view = SavedView::load(BufReader::new(File::open(file)?))?;
+        if view.map_id < 1 || view.map_id > MAP_COUNT {
+            return Err(GameError::ResourceLoadError(format!(
+                "screenshot: no map {}",
+                view.map_id
+            )));
+        }
+
+        if view.map_id != self.map_id {
+            self.map_id = view.map_id;
+            self.map = Map::new(ctx, self.map_id)?;
+        }
+        self.stop();
+        self.playback = None;
+        self.ground_follow = None;
+        self.draw_param = view.param;
+        self.previous_param = self.draw_param.clone();
         Ok(())
     }
 
     fn update_map(&mut self, ctx: &mut Context, change: i32) -> GameResult {
-        self.map_id = ((self.map_id - 1 + change) % MAP_COUNT + MAP_COUNT) % MAP_COUNT + 1;
+        let map_id = ((self.map_id - 1 + change) % MAP_COUNT + MAP_COUNT) % MAP_COUNT + 1;
+        self.load_map(ctx, map_id)
+    }
+
+    /// Switches to another map, starting over at the default view.
+    fn load_map(&mut self, ctx: &mut Context, map_id: i32) -> GameResult {
+        self.map_id = map_id;
         self.map = Map::new(ctx, self.map_id)?;
+        self.bookmark = None;
         self.reset();
         Ok(())
     }
 }
 
 impl Scene for MapState {
-    fn update(&mut self, state: &mut game::World, ctx: &mut Context) -> GameResult {
-        let dt = ggez::timer::delta(ctx).as_secs_f32();
+    fn update(&mut self, state: &mut game::World, ctx: &mut Context) -> GameResult<Transition> {
+        let dt = state.timestep();
+        self.previous_param = self.draw_param.clone();
+        if let Some((_, time_left)) = &mut self.notice {
+            *time_left -= dt;
+            if *time_left <= 0.0 {
+                self.notice = None;
+            }
+        }
+
+        let current_settings = self.current_settings(ctx, state);
+        if let Some(settings) = &mut self.settings {
+            settings.update(current_settings, dt)?;
+        }
+
+        if self.mouse_look {
+            let (x, y) = state.input.mouse_position();
+            let (width, height) = graphics::drawable_size(ctx);
+            let (dx, dy) = (x - width / 2.0, y - height / 2.0);
+            if dx.abs() > width / 4.0 || dy.abs() > height / 4.0 {
+                self.recenter_cursor(ctx)?;
+            }
+        }
 
         // calculate forward and sideways directions
         let direction =
@@ -164,29 +875,87 @@ impl Scene for MapState {
         self.draw_param.height_scale += state.input.get_axis(Axis::HeightScale) * self.hs_sens * dt;
         self.draw_param.view_distance +=
             state.input.get_axis(Axis::ViewDistance) * self.v_speed * dt;
-        self.draw_param.camera +=
-            direction * state.input.get_axis(Axis::Throttle) * self.speed * dt;
-        self.draw_param.camera +=
-            strafe_direction * state.input.get_axis(Axis::Strafe) * self.speed * dt;
-        *self.draw_param.camera.y_mut() += state.input.get_axis(Axis::Height) * self.h_speed * dt;
         self.draw_param.fov += state.input.get_axis(Axis::Fov) * self.fov_speed * dt;
+        self.draw_param.horizon += state.input.get_axis(Axis::Look) * self.look_speed * dt;
+
+        // positive turn is to the right, which is a decreasing angle
+        let full = 2.0 * PI;
+        self.draw_param.rotation -= state.input.get_axis(Axis::Turn) * self.turn_speed * dt;
+        self.draw_param.rotation = (self.draw_param.rotation % full + full) % full;
 
         self.draw_param.view_distance = self.draw_param.view_distance.max(MIN_VIEW_DISTANCE);
         self.draw_param.fov = self.draw_param.fov.clamp(MIN_FOV, MAX_FOV);
 
-        Ok(())
+        // playback stops the update after the last pose has been drawn
+        if let Some(playback) = &mut self.playback {
+            if !playback.finished() {
+                playback.update(dt, &mut self.draw_param);
+                return Ok(Transition::None);
+            }
+
+            self.playback = None;
+            if self.quit_after_playback {
+                return Ok(Transition::Quit);
+            }
+        }
+
+        let walk = direction * state.input.get_axis(Axis::Throttle)
+            + strafe_direction * state.input.get_axis(Axis::Strafe);
+        match self.movement {
+            MovementMode::Fly => self.fly(state, walk, dt),
+            MovementMode::Walk => {
+                let jump = state.input.get_axis_raw(Axis::Height) > 0.0;
+                self.walker.update(&self.map, &mut self.draw_param.camera, walk, jump, dt);
+            }
+            MovementMode::Helicopter => {
+                self.helicopter.update(
+                    &self.map,
+                    &mut self.draw_param,
+                    &state.input,
+                    self.clearance,
+                    dt,
+                );
+            }
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.update(dt, &self.draw_param);
+        }
+
+        Ok(Transition::None)
     }
 
-    fn draw(&mut self, _state: &mut game::World, ctx: &mut Context) -> GameResult {
+    fn draw(&mut self, state: &mut game::World, ctx: &mut Context) -> GameResult {
         graphics::clear(ctx, SKY);
 
-        self.map.draw(ctx, &self.draw_param)?;
+        let param = if self.interpolate {
+            self.draw_param.interpolate(&self.previous_param, state.interpolation)
+        } else {
+            self.draw_param.clone()
+        };
+        let depth = if self.depth_requested { Some(&mut self.depth) } else { None };
+        self.map.draw(ctx, &param, depth)?;
+        if self.depth_requested {
+            self.depth_requested = false;
+            self.save_depth(ctx, param.view_distance)?;
+        }
+        // capture before the debug text so it stays out of the video, and
+        // only while the map is running so menus over it aren't recorded
+        if let (Some(capture), false) = (&mut self.capture, self.paused) {
+            capture.write_frame(ctx)?;
+        }
         if self.draw_debug {
-            self.debug.draw(&self.draw_param, self.map_id, ctx)?;
+            let cursor = self.pick_cursor(ctx, state);
+            let mode = self.mode_text();
+            self.debug.draw(&self.draw_param, self.map_id, &mode, cursor, &state.binding, ctx)?;
+        }
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            self.save_screenshot(ctx)?;
+        }
+        if let Some((notice, _)) = &self.notice {
+            self.debug.draw_notice(notice, ctx)?;
         }
-
-        graphics::present(ctx)?;
-        ggez::timer::yield_now();
         Ok(())
     }
 
@@ -196,16 +965,100 @@ impl Scene for MapState {
         ctx: &mut Context,
         event: game::Event,
         started: bool,
-    ) -> GameResult {
+    ) -> GameResult<Transition> {
         match event {
-            InputEffect::Button(button) => self.handle_button(ctx, state, button, started)?,
+            InputEffect::Button(button) => return self.handle_button(ctx, state, button, started),
             InputEffect::Axis(_axis, _value) => (),
-            InputEffect::Pointer(x, y, dx, dy) => self.pointer_moved(ctx, state, x, y, dx, dy),
+            InputEffect::Pointer(_, _, dx, dy) => self.pointer_moved(ctx, state, dx, dy),
+        }
+        Ok(Transition::None)
+    }
+
+    fn command(
+        &mut self,
+        state: &mut game::World,
+        ctx: &mut Context,
+        name: &str,
+        args: &[&str],
+    ) -> GameResult<Option<String>> {
+        self.run_command(ctx, state, name, args)
+    }
+
+    fn on_enter(&mut self, state: &mut game::World, _ctx: &mut Context) -> GameResult {
+        Self::register_commands(state);
+        Ok(())
+    }
+
+    /// Saves the settings and finishes any capture in progress.
+    fn on_exit(&mut self, state: &mut game::World, ctx: &mut Context) -> GameResult {
+        Self::unregister_commands(state);
+        self.set_mouse_look(ctx, false)?;
+        if let Some(capture) = self.capture.take() {
+            state.frame_time = None;
+            capture.finish()?;
+        }
+        if let Some(settings) = &mut self.settings {
+            settings.flush()?;
+        }
+        Ok(())
+    }
+
+    fn on_pause(&mut self, _state: &mut game::World, _ctx: &mut Context) -> GameResult {
+        self.paused = true;
+        // the view stops where it is, rather than interpolating back and
+        // forth with the updates of the scene on top
+        self.previous_param = self.draw_param.clone();
+        Ok(())
+    }
+
+    /// Picks up the map chosen in the map browser, or the settings from
+    /// the pause menu.
+    fn on_resume(
+        &mut self,
+        _state: &mut game::World,
+        ctx: &mut Context,
+        result: Option<Box<dyn Any>>,
+    ) -> GameResult {
+        self.paused = false;
+        let result = match result {
+            Some(result) => result,
+            None => return Ok(()),
+        };
+        if let Some(&MapChoice(map_id)) = result.downcast_ref() {
+            self.load_map(ctx, map_id)?;
+        } else if let Some(&tuning) = result.downcast_ref::<Tuning>() {
+            self.apply_tuning(tuning);
+        }
+        Ok(())
+    }
+
+    /// Picks up slider changes from the pause menu while it's still open,
+    /// so they show on the map behind it.
+    fn on_notify(
+        &mut self,
+        _state: &mut game::World,
+        _ctx: &mut Context,
+        message: Box<dyn Any>,
+    ) -> GameResult {
+        if let Some(&tuning) = message.downcast_ref::<Tuning>() {
+            self.apply_tuning(tuning);
         }
         Ok(())
     }
 }
 
+/// Renames a file that couldn't be read to `<name>.bak`, so that saving
+/// the defaults in its place doesn't throw away what was in it.
+fn back_up_broken_file(path: &Path) {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    let backup = PathBuf::from(backup);
+    match fs::rename(path, &backup) {
+        Ok(()) => eprintln!("the broken file was moved to {}", backup.display()),
+        Err(err) => eprintln!("{}: couldn't move the broken file aside: {}", path.display(), err),
+    }
+}
+
 fn vec3_fmt(v: &Vec3, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
     write!(fmt, "({:.2}, {:.2}, {:.2})", &v.x(), &v.y(), &v.z())
 }
@@ -0,0 +1,313 @@
+use ggez::event::KeyCode;
+use ggez::graphics;
+use ggez::graphics::Align;
+use ggez::graphics::Color;
+use ggez::graphics::DrawMode;
+use ggez::graphics::DrawParam;
+use ggez::graphics::Font;
+use ggez::graphics::Mesh;
+use ggez::graphics::Rect;
+use ggez::graphics::Text;
+use ggez::graphics::TextFragment;
+use ggez::Context;
+use ggez::GameResult;
+use glam::*;
+
+use super::MAX_FOV;
+use super::MIN_FOV;
+use super::MIN_VIEW_DISTANCE;
+use super::TO_RADIANS;
+use crate::game::input::types::InputType;
+use crate::game::input::Event;
+use crate::game::Scene;
+use crate::game::Transition;
+use crate::game::World;
+
+const BACKDROP: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 0.6 };
+const TEXT: Color = Color { r: 0.85, g: 0.85, b: 0.85, a: 1.0 };
+const SELECTED: Color = Color { r: 1.0, g: 0.85, b: 0.3, a: 1.0 };
+const FONT_SIZE: f32 = 16.0;
+const MAIN_INSTRUCTIONS: &str = "↑↓: Select  Enter: Choose  Escape: Resume";
+const SETTINGS_INSTRUCTIONS: &str = "↑↓: Select  ←→: Adjust  Escape: Back";
+/// Characters in a slider's bar
+const SLIDER_WIDTH: usize = 20;
+
+/// The view and movement settings the pause menu can change.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tuning {
+    pub fov: f32,
+    pub view_distance: f32,
+    pub height_scale: f32,
+    pub speed: f32,
+    pub mouse_sensitivity: f32,
+    pub detail: f32,
+}
+
+/// One of the settings, shown as a bar between `min` and `max` in the
+/// units it's displayed in.
+struct Slider {
+    label: &'static str,
+    unit: &'static str,
+    min: f32,
+    max: f32,
+    step: f32,
+    get: fn(&Tuning) -> f32,
+    set: fn(&mut Tuning, f32),
+}
+
+const SLIDERS: &[Slider] = &[
+    Slider {
+        label: "Field of View",
+        unit: "°",
+        min: MIN_FOV / TO_RADIANS,
+        max: MAX_FOV / TO_RADIANS,
+        step: 5.0,
+        get: |tuning| tuning.fov / TO_RADIANS,
+        set: |tuning, value| tuning.fov = value * TO_RADIANS,
+    },
+    Slider {
+        label: "View Distance",
+        unit: "",
+        min: MIN_VIEW_DISTANCE,
+        max: 3000.0,
+        step: 50.0,
+        get: |tuning| tuning.view_distance,
+        set: |tuning, value| tuning.view_distance = value,
+    },
+    Slider {
+        label: "Height Scale",
+        unit: "",
+        min: 50.0,
+        max: 1000.0,
+        step: 25.0,
+        get: |tuning| tuning.height_scale,
+        set: |tuning, value| tuning.height_scale = value,
+    },
+    Slider {
+        label: "Movement Speed",
+        unit: "",
+        min: 10.0,
+        max: 500.0,
+        step: 5.0,
+        get: |tuning| tuning.speed,
+        set: |tuning, value| tuning.speed = value,
+    },
+    Slider {
+        label: "Mouse Sensitivity",
+        unit: "°",
+        min: 0.01,
+        max: 1.0,
+        step: 0.01,
+        get: |tuning| tuning.mouse_sensitivity / TO_RADIANS,
+        set: |tuning, value| tuning.mouse_sensitivity = value * TO_RADIANS,
+    },
+    Slider {
+        label: "Render Detail",
+        unit: "",
+        min: 25.0,
+        max: 500.0,
+        step: 25.0,
+        get: |tuning| tuning.detail,
+        set: |tuning, value| tuning.detail = value,
+    },
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Page {
+    Main,
+    Settings,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Entry {
+    Resume,
+    Settings,
+    Quit,
+}
+
+const ENTRIES: [Entry; 3] = [Entry::Resume, Entry::Settings, Entry::Quit];
+
+/// Sits over the paused map with the option to resume, change settings or
+/// quit. Slider changes are handed to the map as soon as they're made, so
+/// their effect shows behind the menu.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct PauseMenu {
+    #[derivative(Debug = "ignore")]
+    font: Font,
+    page: Page,
+    /// Entry on the main page, or slider on the settings page, with the
+    /// one past the last slider being Back
+    selected: usize,
+    tuning: Tuning,
+    #[derivative(Debug = "ignore")]
+    next: Option<Transition>,
+}
+
+impl PauseMenu {
+    pub fn new(ctx: &mut Context, tuning: Tuning) -> GameResult<Self> {
+        Ok(PauseMenu {
+            font: Font::new(ctx, "/LiberationMono-Regular.ttf")?,
+            page: Page::Main,
+            selected: 0,
+            tuning,
+            next: None,
+        })
+    }
+
+    /// Closes the menu, handing the settings back to the map.
+    fn resume(&mut self) {
+        self.next = Some(Transition::Return(Box::new(self.tuning)));
+    }
+
+    fn open(&mut self, page: Page) {
+        self.page = page;
+        self.selected = 0;
+    }
+
+    /// Moves the selected slider by `steps` steps.
+    fn adjust(&mut self, steps: f32) {
+        let slider = match SLIDERS.get(self.selected) {
+            Some(slider) => slider,
+            None => return,
+        };
+        let value = (slider.get)(&self.tuning) + slider.step * steps;
+        // snapped to whole steps, so repeated presses don't drift
+        let value = ((value / slider.step).round() * slider.step).clamp(slider.min, slider.max);
+        (slider.set)(&mut self.tuning, value);
+        self.next = Some(Transition::Notify(Box::new(self.tuning)));
+    }
+
+    fn navigate(&mut self, key: KeyCode) {
+        let count = match self.page {
+            Page::Main => ENTRIES.len(),
+            Page::Settings => SLIDERS.len() + 1,
+        };
+        match key {
+            KeyCode::Up => self.selected = (self.selected + count - 1) % count,
+            KeyCode::Down => self.selected = (self.selected + 1) % count,
+            _ => (),
+        }
+
+        match (self.page, key) {
+            (Page::Main, KeyCode::Return) => match ENTRIES[self.selected] {
+                Entry::Resume => self.resume(),
+                Entry::Settings => self.open(Page::Settings),
+                Entry::Quit => self.next = Some(Transition::Quit),
+            },
+            (Page::Main, KeyCode::Escape) => self.resume(),
+            (Page::Settings, KeyCode::Left) => self.adjust(-1.0),
+            (Page::Settings, KeyCode::Right) => self.adjust(1.0),
+            (Page::Settings, KeyCode::Return) if self.selected == SLIDERS.len() => {
+                self.open(Page::Main)
+            }
+            (Page::Settings, KeyCode::Escape) => self.open(Page::Main),
+            _ => (),
+        }
+    }
+
+    /// The lines of the current page, and whether each is selected.
+    fn lines(&self) -> Vec<(String, bool)> {
+        match self.page {
+            Page::Main => ENTRIES
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| (format!("{:?}", entry), i == self.selected))
+                .collect(),
+            Page::Settings => {
+                let mut lines: Vec<(String, bool)> = SLIDERS
+                    .iter()
+                    .enumerate()
+                    .map(|(i, slider)| (slider_text(slider, &self.tuning), i == self.selected))
+                    .collect();
+                lines.push(("Back".to_owned(), self.selected == SLIDERS.len()));
+                lines
+            }
+        }
+    }
+}
+
+impl Scene for PauseMenu {
+    fn update(&mut self, _state: &mut World, _ctx: &mut Context) -> GameResult<Transition> {
+        Ok(self.next.take().unwrap_or(Transition::None))
+    }
+
+    fn draw(&mut self, _state: &mut World, ctx: &mut Context) -> GameResult {
+        let (width, height) = graphics::drawable_size(ctx);
+        let backdrop = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, width, height),
+            BACKDROP,
+        )?;
+        graphics::draw(ctx, &backdrop, DrawParam::new())?;
+
+        let fragment = |text: String, color: Color| {
+            TextFragment::new(text).color(color).font(self.font).scale(FONT_SIZE)
+        };
+
+        let (title, instructions) = match self.page {
+            Page::Main => ("Paused", MAIN_INSTRUCTIONS),
+            Page::Settings => ("Settings", SETTINGS_INSTRUCTIONS),
+        };
+        let mut text = Text::default();
+        text.add(fragment(format!("{}\n{}\n\n", title, instructions), TEXT));
+        for (line, selected) in self.lines() {
+            let (marker, color) = if selected { ('>', SELECTED) } else { (' ', TEXT) };
+            text.add(fragment(format!("{} {}\n", marker, line), color));
+        }
+
+        text.set_bounds(Vec2::new(width, height), Align::Center);
+        graphics::draw(ctx, &text, (Vec2::new(0.0, height / 4.0),))?;
+        Ok(())
+    }
+
+    fn input(
+        &mut self,
+        _state: &mut World,
+        _ctx: &mut Context,
+        _event: Event,
+        _started: bool,
+    ) -> GameResult<Transition> {
+        Ok(Transition::None)
+    }
+
+    /// Takes every input, so nothing reaches the paused map.
+    fn raw_input(
+        &mut self,
+        _state: &mut World,
+        _ctx: &mut Context,
+        input: InputType,
+        started: bool,
+    ) -> GameResult<bool> {
+        if let (true, InputType::KeyEvent(key)) = (started, input) {
+            self.navigate(key);
+        }
+        Ok(true)
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+
+    /// Keys held when the menu closes were let go of while it was open.
+    fn on_exit(&mut self, state: &mut World, _ctx: &mut Context) -> GameResult {
+        state.input.reset_input_state();
+        Ok(())
+    }
+}
+
+/// A slider's label, bar and value, padded so that the bars line up.
+fn slider_text(slider: &Slider, tuning: &Tuning) -> String {
+    let value = (slider.get)(tuning);
+    let fraction = (value - slider.min) / (slider.max - slider.min);
+    let filled = ((fraction * SLIDER_WIDTH as f32).round() as usize).min(SLIDER_WIDTH);
+    let decimals = if slider.step < 1.0 { 2 } else { 0 };
+    format!(
+        "{:<18}[{}{}] {:>7}",
+        slider.label,
+        "#".repeat(filled),
+        "-".repeat(SLIDER_WIDTH - filled),
+        format!("{:.*}{}", decimals, value, slider.unit)
+    )
+}
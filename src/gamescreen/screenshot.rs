@@ -0,0 +1,161 @@
+use std::io::Read;
+use std::io::Write;
+
+use ggez::GameError;
+use ggez::GameResult;
+use glam::*;
+
+use super::MapDrawParam;
+
+const KEYWORD_PREFIX: &str = "voxelspace:";
+
+/// The map and camera a screenshot was taken with, stored in its PNG text
+/// chunks so that opening the screenshot brings back the exact same view.
+///
+/// Every value gets its own `voxelspace:<name>` chunk. Numbers are written
+/// with enough digits to read back the same `f32`.
+#[derive(Debug, Clone)]
+pub struct SavedView {
+    pub map_id: i32,
+    pub param: MapDrawParam,
+}
+
+impl SavedView {
+    /// Encodes an RGBA image as PNG with this view embedded.
+    pub fn write_png<W: Write>(
+        &self,
+        writer: W,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> GameResult {
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut param = self.param.clone();
+        let camera = param.camera;
+        let mut chunks = vec![
+            ("map", self.map_id.to_string()),
+            ("camera", format!("{} {} {}", camera.x(), camera.y(), camera.z())),
+        ];
+        for (name, value) in param.scalar_fields() {
+            chunks.push((name, value.to_string()));
+        }
+        for (name, text) in chunks {
+            encoder
+                .add_text_chunk(format!("{}{}", KEYWORD_PREFIX, name), text)
+                .map_err(png_error)?;
+        }
+
+        let mut png_writer = encoder.write_header().map_err(png_error)?;
+        png_writer.write_image_data(rgba).map_err(png_error)?;
+        Ok(())
+    }
+
+    /// Reads the view back out of a screenshot. Values missing from the
+    /// file keep their defaults, but the map and camera position have to be
+    /// there.
+    pub fn load<R: Read>(reader: R) -> GameResult<Self> {
+        let png_reader = png::Decoder::new(reader)
+            .read_info()
+            .map_err(|err| load_error(&format!("failed to decode png: {}", err)))?;
+
+        let mut map_id = None;
+        let mut param = MapDrawParam::default();
+        let mut has_camera = false;
+        for chunk in &png_reader.info().uncompressed_latin1_text {
+            let name = match chunk.keyword.strip_prefix(KEYWORD_PREFIX) {
+                Some(name) => name,
+                None => continue,
+            };
+            let text = chunk.text.trim();
+            let invalid = || load_error(&format!("invalid {} {:?}", name, text));
+
+            match name {
+                "map" => map_id = Some(text.parse().map_err(|_| invalid())?),
+                "camera" => {
+                    let values = text
+                        .split_whitespace()
+                        .map(|value| value.parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| invalid())?;
+                    if values.len() != 3 {
+                        return Err(invalid());
+                    }
+                    param.camera = Vec3::new(values[0], values[1], values[2]);
+                    has_camera = true;
+                }
+                _ => {
+                    // unknown names may come from newer versions, skip them
+                    if let Some((_, value)) =
+                        param.scalar_fields().into_iter().find(|(field, _)| *field == name)
+                    {
+                        *value = text.parse().map_err(|_| invalid())?;
+                    }
+                }
+            }
+        }
+
+        match map_id {
+            Some(map_id) if has_camera => Ok(SavedView { map_id, param }),
+            _ => Err(load_error("not a voxelspace screenshot")),
+        }
+    }
+}
+
+fn load_error(reason: &str) -> GameError {
+    GameError::ResourceLoadError(format!("screenshot: {}", reason))
+}
+
+fn png_error(err: png::EncodingError) -> GameError {
+    GameError::RenderError(format!("failed to encode screenshot: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIXELS: [u8; 16] = [255; 16];
+
+    fn view() -> SavedView {
+        let param = MapDrawParam {
+            camera: Vec3::new(512.25, 180.0, -33.125),
+            rotation: 4.75,
+            horizon: 120.5,
+            pitch: -0.1,
+            roll: 0.2,
+            detail: 275.0,
+            ..MapDrawParam::default()
+        };
+        SavedView { map_id: 17, param }
+    }
+
+    #[test]
+    fn views_survive_a_round_trip() {
+        let mut view = view();
+        let mut file = Vec::new();
+        view.write_png(&mut file, 2, 2, &PIXELS).unwrap();
+        let mut loaded = SavedView::load(&file[..]).unwrap();
+
+        assert_eq!(loaded.map_id, view.map_id);
+        assert_eq!(loaded.param.camera, view.param.camera);
+        for ((name, loaded), (_, saved)) in
+            loaded.param.scalar_fields().into_iter().zip(view.param.scalar_fields())
+        {
+            assert_eq!(*loaded, *saved, "{} changed", name);
+        }
+        assert_eq!(loaded.param.detail, 275.0);
+    }
+
+    #[test]
+    fn plain_pngs_are_errors() {
+        let mut file = Vec::new();
+        let mut encoder = png::Encoder::new(&mut file, 2, 2);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(&PIXELS).unwrap();
+        assert!(SavedView::load(&file[..]).is_err());
+        assert!(SavedView::load(&b"not a png"[..]).is_err());
+    }
+}
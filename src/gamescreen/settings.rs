@@ -0,0 +1,434 @@
+use std::fs;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::PathBuf;
+
+use ggez::Context;
+use ggez::GameError;
+use ggez::GameResult;
+
+use super::DEFAULT_FOV;
+use super::DEFAULT_FOV_SPEED;
+use super::DEFAULT_HS_SENS;
+use super::DEFAULT_H_SPEED;
+use super::DEFAULT_MOUSE_SENSITIVITY;
+use super::DEFAULT_SPEED;
+use super::DEFAULT_V_SPEED;
+use super::MAP_COUNT;
+use super::TO_RADIANS;
+use crate::cli::Args;
+use crate::game::input::types::AnalogResponse;
+use crate::game::DEFAULT_TICK_RATE;
+use crate::game::MAX_TICK_RATE;
+use crate::game::MIN_TICK_RATE;
+
+const SETTINGS_FILE: &str = "settings.cfg";
+const SETTINGS_VERSION: u32 = 1;
+/// Seconds a change has to stick before it's written out, so holding down
+/// a key doesn't rewrite the file every frame
+const SAVE_DELAY: f32 = 1.0;
+const DEFAULT_MAP: i32 = 3;
+const DEFAULT_WIDTH: f32 = 800.0;
+const DEFAULT_HEIGHT: f32 = 600.0;
+
+pub const PLAY_USAGE: &str = "usage: voxelspace [bench|record] [SCREENSHOT.png] \
+[--record-input FILE] [--replay-input FILE] [--SETTING VALUE]...
+settings: map, fov, speed, h_speed, v_speed, fov_speed, hs_sens, mouse_sensitivity,
+invert_mouse, tick_rate, interpolate, window, draw_debug, deadzone, response_curve";
+
+/// Everything about the game that's remembered between launches.
+///
+/// Stored in the user config directory as `key = value` lines, with angles
+/// in degrees. The first line holds the version of the format so that files
+/// from older versions can be upgraded when it changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub map_id: i32,
+    pub fov: f32,
+    pub speed: f32,
+    pub h_speed: f32,
+    pub v_speed: f32,
+    pub fov_speed: f32,
+    pub hs_sens: f32,
+    pub mouse_sensitivity: f32,
+    pub invert_mouse: bool,
+    /// Updates per second
+    pub tick_rate: f32,
+    /// Smooth motion between updates
+    pub interpolate: bool,
+    pub width: f32,
+    pub height: f32,
+    pub draw_debug: bool,
+    /// Gamepad stick shaping
+    pub analog: AnalogResponse,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            map_id: DEFAULT_MAP,
+            fov: DEFAULT_FOV,
+            speed: DEFAULT_SPEED,
+            h_speed: DEFAULT_H_SPEED,
+            v_speed: DEFAULT_V_SPEED,
+            fov_speed: DEFAULT_FOV_SPEED,
+            hs_sens: DEFAULT_HS_SENS,
+            mouse_sensitivity: DEFAULT_MOUSE_SENSITIVITY,
+            invert_mouse: false,
+            tick_rate: DEFAULT_TICK_RATE,
+            interpolate: true,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            draw_debug: true,
+            analog: AnalogResponse::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load<R: BufRead>(reader: R) -> GameResult<Self> {
+        let mut version: Option<u32> = None;
+        let mut values = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse_error = |reason: &str| {
+                GameError::ConfigError(format!(
+                    "settings line {}: {} in {:?}",
+                    index + 1,
+                    reason,
+                    line
+                ))
+            };
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(parse_error("expected key = value")),
+            };
+            if key == "version" {
+                let number = match value.parse() {
+                    Ok(number) if number >= 1 => number,
+                    _ => return Err(parse_error("invalid version")),
+                };
+                if number > SETTINGS_VERSION {
+                    return Err(parse_error("written by a newer version of the game"));
+                }
+                version = Some(number);
+            } else if version.is_none() {
+                return Err(parse_error("expected the version first"));
+            } else {
+                values.push(SettingsLine {
+                    number: index + 1,
+                    key: key.to_owned(),
+                    value: value.to_owned(),
+                });
+            }
+        }
+
+        let mut settings = Settings::default();
+        for line in upgrade(version.unwrap_or(SETTINGS_VERSION), values) {
+            settings.set(&line.key, &line.value).map_err(|reason| {
+                GameError::ConfigError(format!(
+                    "settings line {}: {} in \"{} = {}\"",
+                    line.number, reason, line.key, line.value
+                ))
+            })?;
+        }
+        Ok(settings)
+    }
+
+    pub fn save<W: Write>(&self, mut writer: W) -> GameResult {
+        writeln!(writer, "version = {}", SETTINGS_VERSION)?;
+        for (key, value) in self.values() {
+            writeln!(writer, "{} = {}", key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the value stored under `key`, parsing it the same way as the
+    /// settings file does.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("invalid value {:?} for {}", value, key);
+        let number = || value.parse::<f32>().map_err(|_| invalid());
+        match key {
+            "map" => match value.parse() {
+                Ok(map_id) if (1..=MAP_COUNT).contains(&map_id) => self.map_id = map_id,
+                _ => return Err(invalid()),
+            },
+            "fov" => self.fov = number()? * TO_RADIANS,
+            "speed" => self.speed = number()?,
+            "h_speed" => self.h_speed = number()?,
+            "v_speed" => self.v_speed = number()?,
+            "fov_speed" => self.fov_speed = number()? * TO_RADIANS,
+            "hs_sens" => self.hs_sens = number()?,
+            "mouse_sensitivity" => match number()? {
+                sensitivity if sensitivity > 0.0 => {
+                    self.mouse_sensitivity = sensitivity * TO_RADIANS
+                }
+                _ => return Err(invalid()),
+            },
+            "invert_mouse" => self.invert_mouse = value.parse().map_err(|_| invalid())?,
+            "tick_rate" => match number()? {
+                tick_rate if (MIN_TICK_RATE..=MAX_TICK_RATE).contains(&tick_rate) => {
+                    self.tick_rate = tick_rate
+                }
+                _ => return Err(invalid()),
+            },
+            "interpolate" => self.interpolate = value.parse().map_err(|_| invalid())?,
+            "window" => {
+                let size = value.split_once('x').and_then(|(width, height)| {
+                    Some((width.parse::<f32>().ok()?, height.parse::<f32>().ok()?))
+                });
+                match size {
+                    Some((width, height)) if width > 0.0 && height > 0.0 => {
+                        self.width = width;
+                        self.height = height;
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+            "draw_debug" => self.draw_debug = value.parse().map_err(|_| invalid())?,
+            "deadzone" => match number()? {
+                deadzone if (0.0..1.0).contains(&deadzone) => self.analog.deadzone = deadzone,
+                _ => return Err(invalid()),
+            },
+            "response_curve" => match number()? {
+                curve if curve > 0.0 => self.analog.response_curve = curve,
+                _ => return Err(invalid()),
+            },
+            _ => return Err(format!("unknown setting {}", key)),
+        }
+        Ok(())
+    }
+
+    /// Every setting as it's written to the file.
+    pub fn values(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("map", self.map_id.to_string()),
+            ("fov", (self.fov / TO_RADIANS).to_string()),
+            ("speed", self.speed.to_string()),
+            ("h_speed", self.h_speed.to_string()),
+            ("v_speed", self.v_speed.to_string()),
+            ("fov_speed", (self.fov_speed / TO_RADIANS).to_string()),
+            ("hs_sens", self.hs_sens.to_string()),
+            ("mouse_sensitivity", (self.mouse_sensitivity / TO_RADIANS).to_string()),
+            ("invert_mouse", self.invert_mouse.to_string()),
+            ("tick_rate", self.tick_rate.to_string()),
+            ("interpolate", self.interpolate.to_string()),
+            ("window", format!("{}x{}", self.width, self.height)),
+            ("draw_debug", self.draw_debug.to_string()),
+            ("deadzone", self.analog.deadzone.to_string()),
+            ("response_curve", self.analog.response_curve.to_string()),
+        ]
+    }
+}
+
+/// A `key = value` line of the settings file, remembering where it was for
+/// error messages.
+#[derive(Debug, Clone)]
+struct SettingsLine {
+    number: usize,
+    key: String,
+    value: String,
+}
+
+/// Brings the lines of a file written by an older version of the game up to
+/// the current format. Each version gets an arm that converts its lines to
+/// the next version's and carries on from there, so a file can be upgraded
+/// across several versions at once.
+fn upgrade(version: u32, lines: Vec<SettingsLine>) -> Vec<SettingsLine> {
+    match version {
+        // the current format
+        1 => lines,
+        _ => unreachable!("settings version {} is checked while reading", version),
+    }
+}
+
+/// Settings given on the command line, which win over the settings file.
+#[derive(Debug, Clone, Default)]
+pub struct PlayConfig {
+    /// Screenshot to restore the view from
+    pub screenshot: Option<PathBuf>,
+    /// Where to log the input, to replay the session later
+    pub record_input: Option<PathBuf>,
+    /// Input log to play back instead of the real input
+    pub replay_input: Option<PathBuf>,
+    pub overrides: Vec<(String, String)>,
+}
+
+impl PlayConfig {
+    /// Parses the arguments when not running a subcommand.
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> GameResult<Self> {
+        let mut args = Args::new(args, PLAY_USAGE);
+        let mut config = PlayConfig::default();
+        // check the values up front rather than failing once the window is open
+        let mut check = Settings::default();
+        while let Some(flag) = args.next_flag() {
+            match flag.strip_prefix("--") {
+                Some("record-input") => config.record_input = Some(args.string(&flag)?.into()),
+                Some("replay-input") => config.replay_input = Some(args.string(&flag)?.into()),
+                Some(key) => {
+                    let value = args.string(&flag)?;
+                    check.set(key, &value).map_err(|reason| args.error(&reason))?;
+                    config.overrides.push((key.to_owned(), value));
+                }
+                None if flag.ends_with(".png") && config.screenshot.is_none() => {
+                    config.screenshot = Some(PathBuf::from(flag))
+                }
+                None => return Err(args.error(&format!("unknown argument {}", flag))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Keeps the settings file in sync with the game.
+///
+/// Values overridden on the command line only last for the current run, the
+/// file keeps whatever it had for them.
+#[derive(Debug)]
+pub struct SettingsStore {
+    path: PathBuf,
+    /// What's in the file
+    saved: Settings,
+    overrides: Vec<(String, String)>,
+    /// The game's settings when they last changed
+    current: Settings,
+    /// Seconds since an unsaved change
+    unsaved_for: Option<f32>,
+}
+
+impl SettingsStore {
+    /// Reads the settings file, falling back to the defaults if there isn't
+    /// one yet. A broken file also falls back to the defaults, reporting
+    /// what's wrong with it and moving it aside before it's saved over.
+    pub fn load(ctx: &Context, overrides: Vec<(String, String)>) -> GameResult<Self> {
+        let path = ggez::filesystem::user_config_dir(ctx).join(SETTINGS_FILE);
+        let saved = if path.exists() {
+            File::open(&path)
+                .map_err(GameError::from)
+                .and_then(|file| Settings::load(BufReader::new(file)))
+                .unwrap_or_else(|err| {
+                    eprintln!("{}: {}, using the default settings", path.display(), err);
+                    super::back_up_broken_file(&path);
+                    Settings::default()
+                })
+        } else {
+            Settings::default()
+        };
+
+        let mut current = saved.clone();
+        for (key, value) in &overrides {
+            current.set(key, value).map_err(GameError::ConfigError)?;
+        }
+        Ok(SettingsStore { path, saved, overrides, current, unsaved_for: None })
+    }
+
+    /// The settings to start the game with.
+    pub fn settings(&self) -> &Settings {
+        &self.current
+    }
+
+    /// Takes the game's current settings, saving them once they've stopped
+    /// changing for a moment.
+    pub fn update(&mut self, settings: Settings, dt: f32) -> GameResult {
+        if settings != self.current {
+            self.current = settings;
+            self.unsaved_for = Some(0.0);
+        } else if let Some(unsaved_for) = &mut self.unsaved_for {
+            *unsaved_for += dt;
+            if *unsaved_for >= SAVE_DELAY {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves any changes right away.
+    pub fn flush(&mut self) -> GameResult {
+        if self.unsaved_for.take().is_none() {
+            return Ok(());
+        }
+
+        let mut settings = self.current.clone();
+        for (key, _) in &self.overrides {
+            let saved = self.saved.values().into_iter().find(|(name, _)| name == key);
+            if let Some((_, value)) = saved {
+                settings.set(key, &value).map_err(GameError::ConfigError)?;
+            }
+        }
+        if settings == self.saved {
+            return Ok(());
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        settings.save(File::create(&self.path)?)?;
+        self.saved = settings;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(file: &str) -> GameResult<Settings> {
+        Settings::load(file.as_bytes())
+    }
+
+    #[test]
+    fn settings_survive_a_round_trip() {
+        let settings = Settings {
+            map_id: 7,
+            invert_mouse: true,
+            width: 1280.0,
+            height: 720.0,
+            analog: AnalogResponse { deadzone: 0.25, response_curve: 2.0 },
+            ..Settings::default()
+        };
+
+        let mut file = Vec::new();
+        settings.save(&mut file).unwrap();
+        let loaded = Settings::load(&file[..]).unwrap();
+        assert_eq!(loaded.map_id, 7);
+        assert!(loaded.invert_mouse);
+        assert_eq!((loaded.width, loaded.height), (1280.0, 720.0));
+        assert_eq!(loaded.analog, settings.analog);
+    }
+
+    #[test]
+    fn v1_files_load_as_they_are() {
+        let settings = load("version = 1\nmap = 12\nspeed = 20\nwindow = 640x480\n").unwrap();
+        assert_eq!(settings.map_id, 12);
+        assert_eq!(settings.speed, 20.0);
+        assert_eq!((settings.width, settings.height), (640.0, 480.0));
+        assert_eq!(settings.fov, Settings::default().fov);
+    }
+
+    #[test]
+    fn versions_are_checked() {
+        assert!(load(&format!("version = {}\nmap = 2\n", SETTINGS_VERSION + 1)).is_err());
+        assert!(load("version = 0\nmap = 2\n").is_err());
+        assert!(load("version = new\nmap = 2\n").is_err());
+        assert!(load("map = 2\nversion = 1\n").is_err());
+    }
+
+    #[test]
+    fn bad_lines_are_errors() {
+        assert!(load("version = 1\nmap 2\n").is_err());
+        assert!(load("version = 1\nmap = 0\n").is_err());
+        assert!(load("version = 1\nspeed = fast\n").is_err());
+        assert!(load("version = 1\nwindow = 640\n").is_err());
+        assert!(load("version = 1\ntick_rate = 0.5\n").is_err());
+        assert!(load("version = 1\ntick_rate = 100000\n").is_err());
+        assert!(load("version = 1\ngravity = 9.8\n").is_err());
+    }
+}
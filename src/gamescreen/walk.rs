@@ -0,0 +1,112 @@
+use glam::*;
+
+use super::map::Map;
+
+const DEFAULT_WALK_SPEED: f32 = 30.0;
+const DEFAULT_EYE_HEIGHT: f32 = 8.0;
+const DEFAULT_GRAVITY: f32 = 150.0;
+const DEFAULT_JUMP_SPEED: f32 = 45.0;
+const DEFAULT_MAX_SLOPE: f32 = 1.0;
+const DEFAULT_SLIDE_SPEED: f32 = 40.0;
+
+/// First person movement over the terrain, with gravity and jumping.
+#[derive(Debug)]
+pub struct Walker {
+    pub speed: f32,
+    /// Height of the camera above the feet
+    pub eye_height: f32,
+    pub gravity: f32,
+    /// Upwards speed at the start of a jump
+    pub jump_speed: f32,
+    /// Steepest slope that can be walked up, as rise over run
+    pub max_slope: f32,
+    /// Speed at which the walker slides down slopes steeper than `max_slope`
+    pub slide_speed: f32,
+    vertical_speed: f32,
+    on_ground: bool,
+}
+
+impl Default for Walker {
+    fn default() -> Self {
+        Walker {
+            speed: DEFAULT_WALK_SPEED,
+            eye_height: DEFAULT_EYE_HEIGHT,
+            gravity: DEFAULT_GRAVITY,
+            jump_speed: DEFAULT_JUMP_SPEED,
+            max_slope: DEFAULT_MAX_SLOPE,
+            slide_speed: DEFAULT_SLIDE_SPEED,
+            vertical_speed: 0.0,
+            on_ground: false,
+        }
+    }
+}
+
+impl Walker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_ground(&self) -> bool {
+        self.on_ground
+    }
+
+    /// Drops any vertical momentum, e.g. after the camera was moved by hand.
+    pub fn stop(&mut self) {
+        self.vertical_speed = 0.0;
+        self.on_ground = false;
+    }
+
+    /// Moves the camera by `walk`, a unit-ish direction on the x/z plane,
+    /// then applies gravity and resolves contact with the terrain.
+    pub fn update(&mut self, map: &Map, camera: &mut Vec3, walk: Vec3, jump: bool, dt: f32) {
+        let gradient = slope_gradient(map, camera.x(), camera.z());
+        let sliding = self.on_ground && gradient.length() > self.max_slope;
+
+        let step = if sliding {
+            // too steep to stand on, slide downhill
+            -gradient.normalize() * self.slide_speed * dt
+        } else {
+            walk * self.speed * dt
+        };
+        let distance = step.length();
+        let target = *camera + step;
+        let rise = map.height_at(target.x(), target.z()) - map.height_at(camera.x(), camera.z());
+        // refuse steps that climb too steeply, walking down is always fine
+        if sliding || !self.on_ground || distance == 0.0 || rise / distance <= self.max_slope {
+            camera.set_x(target.x());
+            camera.set_z(target.z());
+        }
+
+        if jump && self.on_ground && !sliding {
+            self.vertical_speed = self.jump_speed;
+        }
+
+        self.vertical_speed -= self.gravity * dt;
+        *camera.y_mut() += self.vertical_speed * dt;
+
+        // stick to the ground when walking down walkable slopes instead of
+        // hopping off of every step
+        let snap = if self.on_ground && self.vertical_speed <= 0.0 {
+            distance * self.max_slope.max(gradient.length())
+        } else {
+            0.0
+        };
+
+        let eye_level = map.height_at(camera.x(), camera.z()) + self.eye_height;
+        if camera.y() <= eye_level + snap {
+            camera.set_y(eye_level);
+            self.vertical_speed = 0.0;
+            self.on_ground = true;
+        } else {
+            self.on_ground = false;
+        }
+    }
+}
+
+/// Direction of steepest ascent of the terrain on the x/z plane, with the
+/// slope (rise over run) as its length.
+fn slope_gradient(map: &Map, x: f32, z: f32) -> Vec3 {
+    let dx = (map.height_at(x + 1.0, z) - map.height_at(x - 1.0, z)) / 2.0;
+    let dz = (map.height_at(x, z + 1.0) - map.height_at(x, z - 1.0)) / 2.0;
+    Vec3::new(dx, 0.0, dz)
+}
@@ -1,7 +1,10 @@
+use game::replay::InputRecorder;
+use game::replay::InputReplay;
 use game::Game;
 use game::Scene;
 use ggez::{self};
 
+pub mod cli;
 pub mod game;
 pub mod gamescreen;
 
@@ -13,16 +16,90 @@ use std::env;
 use ggez::event;
 use ggez::GameResult;
 
+/// What to do, picked by the first command line argument.
+enum Command {
+    Play(gamescreen::PlayConfig),
+    Bench(gamescreen::BenchConfig),
+    Record(gamescreen::RecordConfig),
+}
+
 pub fn main() -> GameResult {
+    let mut args = env::args().skip(1);
+    let command = match args.next().as_deref() {
+        Some("bench") => Command::Bench(gamescreen::BenchConfig::from_args(args)?),
+        Some("record") => Command::Record(gamescreen::RecordConfig::from_args(args)?),
+        first => {
+            let args = first.map(str::to_owned).into_iter().chain(args);
+            Command::Play(gamescreen::PlayConfig::from_args(args)?)
+        }
+    };
+
+    // benchmarks and recordings run at a fixed size without waiting for vsync
+    let (window_mode, vsync) = match &command {
+        Command::Play(_) => {
+            (ggez::conf::WindowMode::default().dimensions(800.0, 600.0).resizable(true), true)
+        }
+        Command::Bench(config) => {
+            (ggez::conf::WindowMode::default().dimensions(config.width, config.height), false)
+        }
+        Command::Record(config) => {
+            (ggez::conf::WindowMode::default().dimensions(config.width, config.height), false)
+        }
+    };
+
     let zipped_resources = include_bytes!(concat!(env!("OUT_DIR"), "/resources.zip"));
     let cb = ggez::ContextBuilder::new("voxelspace", "qmatias")
         .add_zipfile_bytes(&zipped_resources[..]) // need a slice here or it won't compile
-        .window_setup(ggez::conf::WindowSetup::default().title("Voxel Space Demo"))
-        .window_mode(ggez::conf::WindowMode::default().dimensions(800.0, 600.0).resizable(true));
+        .window_setup(ggez::conf::WindowSetup::default().title("Voxel Space Demo").vsync(vsync))
+        .window_mode(window_mode);
 
     let (mut ctx, event_loop) = cb.build()?;
+    let mut world = game::World::new(game::input::controls::load_controls(&ctx));
+    let mut recorder = None;
+    let mut replay = None;
 
-    let scenes: Vec<Box<dyn Scene>> = vec![Box::new(gamescreen::MapState::new(&mut ctx, 3)?)];
-    let game = Game::new(scenes);
+    let scenes: Vec<Box<dyn Scene>> = match command {
+        Command::Play(config) => {
+            let mut overrides = config.overrides;
+            if let Some(path) = &config.replay_input {
+                // start from the recorded settings, the command line still wins
+                let log = InputReplay::open(path)?;
+                overrides = log.settings().iter().cloned().chain(overrides).collect();
+                replay = Some(log);
+            }
+            let store = gamescreen::SettingsStore::load(&ctx, overrides)?;
+            let settings = store.settings();
+            if let Some(path) = &config.record_input {
+                let values: Vec<(String, String)> = settings
+                    .values()
+                    .into_iter()
+                    .map(|(key, value)| (key.to_owned(), value))
+                    .collect();
+                recorder = Some(InputRecorder::create(path, &values)?);
+            }
+            world.input.analog = settings.analog;
+            world.tick_rate = settings.tick_rate;
+            ggez::graphics::set_drawable_size(&mut ctx, settings.width, settings.height)?;
+            let mut state = gamescreen::MapState::new(&mut ctx, settings.map_id)?;
+            state.use_settings(&mut ctx, store)?;
+            if let Some(file) = config.screenshot {
+                state.restore_screenshot(&mut ctx, &file)?;
+            }
+            vec![Box::new(state)]
+        }
+        Command::Bench(config) => vec![Box::new(gamescreen::BenchState::new(&mut ctx, config)?)],
+        Command::Record(config) => {
+            let mut state = gamescreen::MapState::new(&mut ctx, config.map_id)?;
+            state.record_video(&mut ctx, &mut world, &config)?;
+            vec![Box::new(state)]
+        }
+    };
+    let mut game = Game::new(&mut ctx, scenes, world)?;
+    if let Some(recorder) = recorder {
+        game.record_input(recorder);
+    }
+    if let Some(replay) = replay {
+        game.replay_input(replay);
+    }
     event::run(ctx, event_loop, game);
 }